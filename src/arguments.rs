@@ -0,0 +1,249 @@
+use crate::error::{Error, Result};
+use std::collections::BTreeMap;
+
+/// 指示子の引数を空白で区切る
+///
+/// `"..."`または`'...'`で囲んだ部分は空白を含めて1つの項として扱う。
+pub fn split(argument: &str) -> Result<Vec<String>> {
+    let mut terms = Vec::new();
+    let mut current = String::new();
+    let mut in_term = false;
+    let mut quote = None;
+
+    for c in argument.chars() {
+        match quote {
+            Some(q) if c == q => quote = None,
+            Some(_) => current.push(c),
+            None if c == '"' || c == '\'' => {
+                quote = Some(c);
+                in_term = true;
+            }
+            None if c.is_whitespace() => {
+                if in_term {
+                    terms.push(std::mem::take(&mut current));
+                    in_term = false;
+                }
+            }
+            None => {
+                current.push(c);
+                in_term = true;
+            }
+        }
+    }
+
+    if quote.is_some() {
+        return Err(Error::InvalidDirectiveArgument {
+            argument: argument.to_string(),
+            message: "unterminated quote".to_string(),
+        });
+    }
+
+    if in_term {
+        terms.push(current);
+    }
+
+    Ok(terms)
+}
+
+/// 指示子の`key=value`形式のオプション
+///
+/// 取り出されなかったオプションは`finish`で未知のオプションとしてエラーにする。
+#[derive(Debug)]
+pub struct Options {
+    argument: String,
+    values: BTreeMap<String, String>,
+}
+
+impl Options {
+    /// 引数を分割し、オプション以外の項とオプションに分ける
+    pub fn parse(argument: &str) -> Result<(Vec<String>, Self)> {
+        let mut terms = Vec::new();
+        let mut values = BTreeMap::new();
+
+        for term in split(argument)? {
+            match term.split_once('=') {
+                Some((key, value)) if is_option_key(key) => {
+                    values.insert(key.to_string(), value.to_string());
+                }
+                _ => terms.push(term),
+            }
+        }
+
+        let options = Self {
+            argument: argument.to_string(),
+            values,
+        };
+        Ok((terms, options))
+    }
+
+    pub fn take_string(&mut self, key: &str) -> Option<String> {
+        self.values.remove(key)
+    }
+
+    pub fn take_bool(&mut self, key: &str) -> Result<Option<bool>> {
+        self.take_parsed(key, |value| match value {
+            "true" | "yes" => Some(true),
+            "false" | "no" => Some(false),
+            _ => None,
+        })
+    }
+
+    pub fn take_usize(&mut self, key: &str) -> Result<Option<usize>> {
+        self.take_parsed(key, |value| value.parse().ok())
+    }
+
+    fn take_parsed<T>(
+        &mut self,
+        key: &str,
+        parse: impl Fn(&str) -> Option<T>,
+    ) -> Result<Option<T>> {
+        let Some(value) = self.values.remove(key) else {
+            return Ok(None);
+        };
+
+        parse(&value)
+            .map(Some)
+            .ok_or_else(|| self.error(format!("invalid value `{}` for option `{}`", value, key)))
+    }
+
+    /// 未知のオプションが残っていればエラーにする
+    pub fn finish(self) -> Result<()> {
+        match self.values.keys().next() {
+            Some(key) => Err(self.error(format!("unknown option `{}`", key))),
+            None => Ok(()),
+        }
+    }
+
+    pub fn error(&self, message: String) -> Error {
+        Error::InvalidDirectiveArgument {
+            argument: self.argument.clone(),
+            message,
+        }
+    }
+}
+
+fn is_option_key(key: &str) -> bool {
+    key.starts_with(|c: char| c.is_ascii_alphabetic())
+        && key
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-')
+}
+
+/// `src/{api,db}/*.rs`のような波括弧による選択肢を展開する
+///
+/// 入れ子にも対応する。カンマを含まない波括弧はそのまま残す。
+pub fn expand_braces(pattern: &str) -> Vec<String> {
+    let Some((start, end, alternatives)) = find_alternation(pattern) else {
+        return vec![pattern.to_string()];
+    };
+
+    let prefix = &pattern[..start];
+    let suffix = &pattern[end + 1..];
+
+    alternatives
+        .into_iter()
+        .flat_map(|alternative| expand_braces(&format!("{}{}{}", prefix, alternative, suffix)))
+        .collect()
+}
+
+/// 最初に現れる、カンマを含む波括弧の位置と選択肢を返す
+fn find_alternation(pattern: &str) -> Option<(usize, usize, Vec<&str>)> {
+    for (start, c) in pattern.char_indices() {
+        if c != '{' {
+            continue;
+        }
+
+        let mut depth = 0;
+        let mut alternatives = Vec::new();
+        let mut alternative_start = start + 1;
+
+        for (i, c) in pattern[start..].char_indices() {
+            let i = start + i;
+            match c {
+                '{' => depth += 1,
+                '}' => {
+                    depth -= 1;
+                    if depth == 0 {
+                        if alternatives.is_empty() {
+                            break;
+                        }
+                        alternatives.push(&pattern[alternative_start..i]);
+                        return Some((start, i, alternatives));
+                    }
+                }
+                ',' if depth == 1 => {
+                    alternatives.push(&pattern[alternative_start..i]);
+                    alternative_start = i + 1;
+                }
+                _ => {}
+            }
+        }
+    }
+
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_split() {
+        assert_eq!(
+            split("src/*.rs  tests/*.rs").unwrap(),
+            vec!["src/*.rs", "tests/*.rs"]
+        );
+        assert_eq!(
+            split(r#""my docs/*.md" 'a b.txt' c"d e"f"#).unwrap(),
+            vec!["my docs/*.md", "a b.txt", "cd ef"]
+        );
+        assert_eq!(split("  ").unwrap(), Vec::<String>::new());
+        assert_eq!(split(r#""""#).unwrap(), vec![""]);
+    }
+
+    #[test]
+    fn test_split_unterminated_quote() {
+        assert!(matches!(
+            split(r#""src/*.rs"#),
+            Err(Error::InvalidDirectiveArgument { .. })
+        ));
+    }
+
+    #[test]
+    fn test_options() {
+        let (terms, mut options) = Options::parse("crate::a root=src/lib.rs tests=false").unwrap();
+        assert_eq!(terms, vec!["crate::a"]);
+        assert_eq!(options.take_string("root"), Some("src/lib.rs".to_string()));
+        assert_eq!(options.take_bool("tests").unwrap(), Some(false));
+        assert_eq!(options.take_string("depth"), None);
+        options.finish().unwrap();
+
+        let (_, mut options) = Options::parse("a tests=maybe depth=1").unwrap();
+        assert!(options.take_bool("tests").is_err());
+        assert_eq!(options.take_usize("depth").unwrap(), Some(1));
+
+        let (_, mut options) = Options::parse("a depth=many").unwrap();
+        assert!(options.take_usize("depth").is_err());
+
+        let (_, options) = Options::parse("a unknown=1").unwrap();
+        assert!(matches!(
+            options.finish(),
+            Err(Error::InvalidDirectiveArgument { .. })
+        ));
+    }
+
+    #[test]
+    fn test_expand_braces() {
+        assert_eq!(
+            expand_braces("src/{api,db}/**/*.rs"),
+            vec!["src/api/**/*.rs", "src/db/**/*.rs"]
+        );
+        assert_eq!(
+            expand_braces("{a,b{c,d}}.{ts,tsx}"),
+            vec!["a.ts", "a.tsx", "bc.ts", "bc.tsx", "bd.ts", "bd.tsx"]
+        );
+        assert_eq!(expand_braces("src/{a}.rs"), vec!["src/{a}.rs"]);
+        assert_eq!(expand_braces("{,x}y"), vec!["y", "xy"]);
+        assert_eq!(expand_braces("plain/*.rs"), vec!["plain/*.rs"]);
+    }
+}
@@ -0,0 +1,254 @@
+use clap::Parser;
+use ef::language::LanguageMappings;
+use serde::Deserialize;
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::PathBuf;
+
+/// github-linguistのlanguages.ymlから、assets/language_mappings.ronを再生成するツール
+#[derive(Parser)]
+#[command(name = "update_languages")]
+#[command(about = "Regenerate assets/language_mappings.ron from a local linguist languages.yml")]
+struct Cli {
+    /// Path to linguist's languages.yml
+    ///
+    /// Download it from https://github.com/github-linguist/linguist/blob/main/lib/linguist/languages.yml beforehand; this tool never accesses the network.
+    #[arg(value_name = "LANGUAGES_YML")]
+    languages_yml: PathBuf,
+
+    /// Path of the RON file to write (usually assets/language_mappings.ron in the repository)
+    #[arg(short, long, value_name = "PATH")]
+    output: PathBuf,
+
+    /// Only report the differences without writing the file
+    #[arg(long)]
+    dry_run: bool,
+}
+
+#[derive(Deserialize, Default)]
+#[serde(default)]
+struct LinguistLanguage {
+    extensions: Vec<String>,
+    filenames: Vec<String>,
+    interpreters: Vec<String>,
+}
+
+fn main() {
+    let cli = Cli::parse();
+
+    if let Err(err) = run(&cli) {
+        eprintln!("Error: {}", err);
+        std::process::exit(1);
+    }
+}
+
+fn run(cli: &Cli) -> Result<(), Box<dyn std::error::Error>> {
+    let yaml = fs::read_to_string(&cli.languages_yml)?;
+    let languages: BTreeMap<String, LinguistLanguage> = serde_yaml::from_str(&yaml)?;
+    let mappings = build_mappings(&languages);
+
+    let current = match fs::read_to_string(&cli.output) {
+        Ok(content) => LanguageMappings::parse(&content)?,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => LanguageMappings::default(),
+        Err(e) => return Err(e.into()),
+    };
+
+    let changes = diff_mappings(&current, &mappings);
+    for change in &changes {
+        eprintln!("{}", change);
+    }
+    eprintln!(
+        "{} extensions, {} filenames, {} interpreters ({} changes)",
+        mappings.extensions.len(),
+        mappings.filenames.len(),
+        mappings.interpreters.len(),
+        changes.len()
+    );
+
+    if !cli.dry_run {
+        fs::write(&cli.output, to_ron(&mappings)?)?;
+        eprintln!("Wrote {}", cli.output.display());
+    }
+
+    Ok(())
+}
+
+/// linguistの言語定義をマッピングに変換する
+///
+/// 同じ拡張子を複数の言語が持つ場合は、その拡張子を最初の（主要な）拡張子として
+/// 挙げている言語を優先し、それでも決まらなければ言語名の辞書順で最初のものを採用する。
+/// 内容による判別は`heuristics`モジュールが担当する。
+fn build_mappings(languages: &BTreeMap<String, LinguistLanguage>) -> LanguageMappings {
+    let mut mappings = LanguageMappings::default();
+    let mut primary: BTreeMap<String, bool> = BTreeMap::new();
+
+    for (name, language) in languages {
+        let name = name.to_lowercase();
+
+        for (i, extension) in language.extensions.iter().enumerate() {
+            let extension = extension.trim_start_matches('.').to_string();
+            let is_primary = i == 0;
+
+            let replace = match primary.get(&extension) {
+                None => true,
+                Some(&existing_is_primary) => is_primary && !existing_is_primary,
+            };
+            if replace {
+                primary.insert(extension.clone(), is_primary);
+                mappings.extensions.insert(extension, name.clone());
+            }
+        }
+
+        for filename in &language.filenames {
+            mappings
+                .filenames
+                .entry(filename.clone())
+                .or_insert_with(|| name.clone());
+        }
+
+        for interpreter in &language.interpreters {
+            mappings
+                .interpreters
+                .entry(interpreter.clone())
+                .or_insert_with(|| name.clone());
+        }
+    }
+
+    mappings
+}
+
+fn diff_mappings(current: &LanguageMappings, updated: &LanguageMappings) -> Vec<String> {
+    let sections = [
+        ("extensions", &current.extensions, &updated.extensions),
+        ("filenames", &current.filenames, &updated.filenames),
+        ("interpreters", &current.interpreters, &updated.interpreters),
+    ];
+
+    let mut changes = Vec::new();
+    for (section, before, after) in sections {
+        for (key, value) in before {
+            match after.get(key) {
+                None => changes.push(format!("- {} {:?}: {:?}", section, key, value)),
+                Some(new_value) if new_value != value => changes.push(format!(
+                    "~ {} {:?}: {:?} -> {:?}",
+                    section, key, value, new_value
+                )),
+                Some(_) => {}
+            }
+        }
+        for (key, value) in after {
+            if !before.contains_key(key) {
+                changes.push(format!("+ {} {:?}: {:?}", section, key, value));
+            }
+        }
+    }
+
+    changes
+}
+
+fn to_ron(mappings: &LanguageMappings) -> Result<String, ron::Error> {
+    ron::ser::to_string_pretty(mappings, ron::ser::PrettyConfig::default())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const LANGUAGES_YML: &str = r#"
+C:
+  type: programming
+  extensions:
+  - ".c"
+  - ".h"
+  interpreters:
+  - tcc
+C++:
+  type: programming
+  extensions:
+  - ".cpp"
+  - ".h"
+  - ".hpp"
+Makefile:
+  type: programming
+  extensions:
+  - ".mk"
+  filenames:
+  - GNUmakefile
+  - Makefile
+  interpreters:
+  - make
+Objective-C:
+  type: programming
+  extensions:
+  - ".m"
+  - ".h"
+Mercury:
+  type: programming
+  extensions:
+  - ".moo"
+  - ".m"
+"#;
+
+    fn parse() -> LanguageMappings {
+        let languages: BTreeMap<String, LinguistLanguage> =
+            serde_yaml::from_str(LANGUAGES_YML).unwrap();
+        build_mappings(&languages)
+    }
+
+    #[test]
+    fn test_build_mappings() {
+        let mappings = parse();
+
+        assert_eq!(mappings.extensions["c"], "c");
+        assert_eq!(mappings.extensions["hpp"], "c++");
+        assert_eq!(mappings.filenames["Makefile"], "makefile");
+        assert_eq!(mappings.interpreters["make"], "makefile");
+        assert_eq!(mappings.interpreters["tcc"], "c");
+    }
+
+    #[test]
+    fn test_conflicting_extensions() {
+        let mappings = parse();
+
+        // 主要な拡張子として挙げている言語が優先される
+        assert_eq!(mappings.extensions["m"], "objective-c");
+        // どの言語の主要な拡張子でもなければ、言語名の辞書順で最初のもの
+        assert_eq!(mappings.extensions["h"], "c");
+    }
+
+    #[test]
+    fn test_output_is_deterministic() {
+        let first = to_ron(&parse()).unwrap();
+        let second = to_ron(&parse()).unwrap();
+
+        assert_eq!(first, second);
+        assert!(first.find("\"c\"").unwrap() < first.find("\"cpp\"").unwrap());
+        assert_eq!(LanguageMappings::parse(&first).unwrap(), parse());
+    }
+
+    #[test]
+    fn test_checked_in_asset_is_canonical() {
+        let path = concat!(env!("CARGO_MANIFEST_DIR"), "/assets/language_mappings.ron");
+        let content = fs::read_to_string(path).unwrap();
+        let mappings = LanguageMappings::parse(&content).unwrap();
+
+        assert_eq!(to_ron(&mappings).unwrap(), content);
+    }
+
+    #[test]
+    fn test_diff_mappings() {
+        let mut before = parse();
+        before.extensions.insert("old".to_string(), "x".to_string());
+        before.extensions.insert("c".to_string(), "c++".to_string());
+
+        let changes = diff_mappings(&before, &parse());
+
+        assert_eq!(
+            changes,
+            vec![
+                "~ extensions \"c\": \"c++\" -> \"c\"".to_string(),
+                "- extensions \"old\": \"x\"".to_string(),
+            ]
+        );
+    }
+}
@@ -0,0 +1,383 @@
+use crate::ignore_rules::repository_root;
+use glob::Pattern;
+use ignore::gitignore::{Gitignore, GitignoreBuilder};
+use once_cell::sync::Lazy;
+use std::cell::RefCell;
+use std::collections::{BTreeMap, HashMap};
+use std::fmt;
+use std::fs;
+use std::io::Read;
+use std::path::{Path, PathBuf};
+
+/// デフォルトで展開しないファイルの種類
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum FileClass {
+    /// ロックファイル、ミニファイされたファイル、コード生成の出力など
+    Generated,
+    /// node_modulesやvendorなど、外部から取り込んだファイル
+    Vendored,
+    Documentation,
+}
+
+impl FileClass {
+    pub const ALL: [FileClass; 3] = [
+        FileClass::Generated,
+        FileClass::Vendored,
+        FileClass::Documentation,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            FileClass::Generated => "generated",
+            FileClass::Vendored => "vendored",
+            FileClass::Documentation => "documentation",
+        }
+    }
+
+    /// `generated,vendored`のようなカンマ区切りの一覧（`all`はすべて）を解釈する
+    pub fn parse_list(value: &str) -> Option<Vec<FileClass>> {
+        let mut classes = Vec::new();
+        for name in value.split(',') {
+            match name {
+                "all" => classes.extend(FileClass::ALL),
+                _ => classes.push(*FileClass::ALL.iter().find(|class| class.name() == name)?),
+            }
+        }
+        Some(classes)
+    }
+
+    /// .gitattributesでこの種類を指定する属性
+    fn attribute(self) -> &'static str {
+        match self {
+            FileClass::Generated => "linguist-generated",
+            FileClass::Vendored => "linguist-vendored",
+            FileClass::Documentation => "linguist-documentation",
+        }
+    }
+}
+
+impl fmt::Display for FileClass {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+/// 生成されたファイルのファイル名
+const GENERATED_FILES: &[&str] = &[
+    "Cargo.lock",
+    "package-lock.json",
+    "npm-shrinkwrap.json",
+    "yarn.lock",
+    "pnpm-lock.yaml",
+    "bun.lockb",
+    "poetry.lock",
+    "Pipfile.lock",
+    "uv.lock",
+    "Gemfile.lock",
+    "composer.lock",
+    "go.sum",
+    "*.min.js",
+    "*.min.css",
+    "*.map",
+    "*.pb.go",
+    "*.pb.h",
+    "*.pb.cc",
+    "*_pb2.py",
+    "*_pb2_grpc.py",
+    "*.g.dart",
+    "*.generated.*",
+];
+
+/// ドキュメントのファイル名（READMEは展開したいことが多いので含めない）
+const DOCUMENTATION_FILES: &[&str] = &[
+    "CHANGELOG*",
+    "CHANGES*",
+    "HISTORY*",
+    "LICENSE*",
+    "LICENCE*",
+    "COPYING*",
+    "NOTICE*",
+    "AUTHORS*",
+    "CONTRIBUTORS*",
+];
+
+const GENERATED_DIRS: &[&str] = &["dist", "__generated__"];
+const VENDORED_DIRS: &[&str] = &[
+    "node_modules",
+    "bower_components",
+    "vendor",
+    "third_party",
+    "third-party",
+    "Pods",
+    "Carthage",
+];
+const DOCUMENTATION_DIRS: &[&str] = &["docs", "doc", "Documentation"];
+
+/// 生成されたファイルの目印（先頭の数行に含まれていれば生成されたものとみなす）
+const GENERATED_MARKERS: &[&str] = &[
+    "@generated",
+    "do not edit",
+    "auto-generated",
+    "autogenerated",
+];
+const MARKER_LINES: usize = 5;
+/// これより長い行（バイト数）があるファイルはミニファイされたものとみなす
+const MAX_LINE_LENGTH: usize = 1000;
+/// 内容による判定で読み込む先頭のバイト数
+const CONTENT_PREFIX: u64 = 8 * 1024;
+
+static GENERATED_PATTERNS: Lazy<Vec<Pattern>> = Lazy::new(|| compile(GENERATED_FILES));
+static DOCUMENTATION_PATTERNS: Lazy<Vec<Pattern>> = Lazy::new(|| compile(DOCUMENTATION_FILES));
+
+fn compile(patterns: &[&str]) -> Vec<Pattern> {
+    patterns
+        .iter()
+        .map(|pattern| Pattern::new(pattern).expect("valid file name pattern"))
+        .collect()
+}
+
+/// .gitattributesの1行（パターンと、それが指定する種類ごとの値）
+struct AttributeRule {
+    matcher: Gitignore,
+    /// `None`は未指定に戻す（`!linguist-generated`）
+    values: Vec<(FileClass, Option<bool>)>,
+}
+
+/// ファイルを生成されたもの・外部のもの・ドキュメントに分類する
+///
+/// 次の順に判定する。
+///
+/// 1. .gitattributesの`linguist-generated`・`linguist-vendored`・`linguist-documentation`
+///    （`-linguist-generated`や`=false`で指定したものは、以降の規則でもその種類にしない）
+/// 2. ファイル名（ロックファイル、`*.min.js`、`*.pb.go`など）とディレクトリ名（`dist/`、`vendor/`、`docs/`など）
+/// 3. 内容（先頭の`@generated`などの目印、非常に長い行）
+pub struct Classifier {
+    /// ディレクトリごとの.gitattributesの規則（ないディレクトリは空）
+    attributes: RefCell<HashMap<PathBuf, Vec<AttributeRule>>>,
+}
+
+impl Classifier {
+    pub fn new() -> Self {
+        Self {
+            attributes: RefCell::new(HashMap::new()),
+        }
+    }
+
+    /// 絶対パス`path`のファイルを分類する
+    ///
+    /// ディレクトリ名の規則は`relative_path`（`path`の末尾の部分）にだけ適用する。
+    /// `#ef vendor/**/*.go`のように明示したディレクトリの中を除外しないため。
+    pub fn classify(&self, path: &Path, relative_path: &Path) -> Option<FileClass> {
+        let attributes = self.attributes_of(path);
+        if let Some(class) = FileClass::ALL
+            .into_iter()
+            .find(|class| attributes.get(class) == Some(&true))
+        {
+            return Some(class);
+        }
+
+        path_class(relative_path)
+            .or_else(|| content_class(path))
+            .filter(|class| attributes.get(class) != Some(&false))
+    }
+
+    /// .gitattributesで指定された種類ごとの値（後に書かれたもの、深いディレクトリのものが優先）
+    fn attributes_of(&self, path: &Path) -> BTreeMap<FileClass, bool> {
+        let mut values = BTreeMap::new();
+        let Some(root) = repository_root(path) else {
+            return values;
+        };
+
+        let dirs = path
+            .ancestors()
+            .skip(1)
+            .take_while(|dir| dir.starts_with(&root))
+            .collect::<Vec<_>>();
+        let mut attributes = self.attributes.borrow_mut();
+        for dir in dirs.into_iter().rev() {
+            let rules = attributes
+                .entry(dir.to_path_buf())
+                .or_insert_with(|| load_attributes(dir));
+            for rule in rules.iter() {
+                if !rule.matcher.matched(path, false).is_ignore() {
+                    continue;
+                }
+                for &(class, value) in &rule.values {
+                    match value {
+                        Some(value) => values.insert(class, value),
+                        None => values.remove(&class),
+                    };
+                }
+            }
+        }
+        values
+    }
+}
+
+/// `dir`の.gitattributesから、分類に関係する行だけを読み込む
+fn load_attributes(dir: &Path) -> Vec<AttributeRule> {
+    let Ok(content) = fs::read_to_string(dir.join(".gitattributes")) else {
+        return Vec::new();
+    };
+
+    content
+        .lines()
+        .filter_map(|line| {
+            let mut fields = line.split_whitespace();
+            let pattern = fields
+                .next()
+                .filter(|pattern| !pattern.starts_with(['#', '[']))?;
+            let values = fields.filter_map(parse_attribute).collect::<Vec<_>>();
+            if values.is_empty() {
+                return None;
+            }
+
+            let mut builder = GitignoreBuilder::new(dir);
+            builder.add_line(None, pattern).ok()?;
+            Some(AttributeRule {
+                matcher: builder.build().ok()?,
+                values,
+            })
+        })
+        .collect()
+}
+
+/// `linguist-generated`・`=true`は設定、`-linguist-generated`・`=false`は解除、`!linguist-generated`は未指定
+fn parse_attribute(attribute: &str) -> Option<(FileClass, Option<bool>)> {
+    let (name, value) = match attribute.split_once('=') {
+        Some((name, value)) => (name, Some(value != "false")),
+        None => match (attribute.strip_prefix('-'), attribute.strip_prefix('!')) {
+            (Some(name), _) => (name, Some(false)),
+            (_, Some(name)) => (name, None),
+            _ => (attribute, Some(true)),
+        },
+    };
+    let class = FileClass::ALL
+        .into_iter()
+        .find(|class| class.attribute() == name)?;
+    Some((class, value))
+}
+
+/// ファイル名とディレクトリ名による分類
+fn path_class(relative_path: &Path) -> Option<FileClass> {
+    let file_name = relative_path.file_name()?.to_string_lossy();
+    let matches = |patterns: &[Pattern]| patterns.iter().any(|pattern| pattern.matches(&file_name));
+
+    if matches(&GENERATED_PATTERNS) {
+        return Some(FileClass::Generated);
+    }
+
+    let dir_class = relative_path
+        .parent()
+        .into_iter()
+        .flat_map(Path::components)
+        .find_map(|component| {
+            let name = component.as_os_str().to_str()?;
+            [
+                (FileClass::Generated, GENERATED_DIRS),
+                (FileClass::Vendored, VENDORED_DIRS),
+                (FileClass::Documentation, DOCUMENTATION_DIRS),
+            ]
+            .into_iter()
+            .find(|(_, dirs)| dirs.contains(&name))
+            .map(|(class, _)| class)
+        });
+    if dir_class.is_some() {
+        return dir_class;
+    }
+
+    matches(&DOCUMENTATION_PATTERNS).then_some(FileClass::Documentation)
+}
+
+/// 内容による分類（生成されたことを示す目印と、ミニファイされたファイルの長い行）
+///
+/// 大きなファイルでも読み込むのは先頭の`CONTENT_PREFIX`バイトだけにする。
+fn content_class(path: &Path) -> Option<FileClass> {
+    let mut content = Vec::new();
+    fs::File::open(path)
+        .ok()?
+        .take(CONTENT_PREFIX)
+        .read_to_end(&mut content)
+        .ok()?;
+    let content = String::from_utf8_lossy(&content);
+
+    let has_marker = content.lines().take(MARKER_LINES).any(|line| {
+        let line = line.to_lowercase();
+        GENERATED_MARKERS.iter().any(|marker| line.contains(marker))
+    });
+    let has_long_line = content.lines().any(|line| line.len() > MAX_LINE_LENGTH);
+
+    (has_marker || has_long_line).then_some(FileClass::Generated)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_path_class() {
+        let class = |path: &str| path_class(Path::new(path));
+
+        assert_eq!(class("Cargo.lock"), Some(FileClass::Generated));
+        assert_eq!(class("web/package-lock.json"), Some(FileClass::Generated));
+        assert_eq!(class("static/app.min.js"), Some(FileClass::Generated));
+        assert_eq!(class("api/user.pb.go"), Some(FileClass::Generated));
+        assert_eq!(class("dist/index.js"), Some(FileClass::Generated));
+        assert_eq!(
+            class("node_modules/react/index.js"),
+            Some(FileClass::Vendored)
+        );
+        assert_eq!(class("vendor/docs/a.go"), Some(FileClass::Vendored));
+        assert_eq!(class("docs/guide.md"), Some(FileClass::Documentation));
+        assert_eq!(class("CHANGELOG.md"), Some(FileClass::Documentation));
+        assert_eq!(class("README.md"), None);
+        assert_eq!(class("src/dist.rs"), None);
+        assert_eq!(class("src/main.rs"), None);
+    }
+
+    #[test]
+    fn test_parse_list() {
+        assert_eq!(
+            FileClass::parse_list("generated,documentation"),
+            Some(vec![FileClass::Generated, FileClass::Documentation])
+        );
+        assert_eq!(FileClass::parse_list("all"), Some(FileClass::ALL.to_vec()));
+        assert_eq!(FileClass::parse_list("tests"), None);
+    }
+
+    #[test]
+    fn test_classify() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path().canonicalize().unwrap();
+        fs::create_dir_all(root.join(".git")).unwrap();
+        fs::create_dir_all(root.join("src/proto")).unwrap();
+        fs::create_dir_all(root.join("docs")).unwrap();
+        fs::write(
+            root.join(".gitattributes"),
+            "src/proto/** linguist-generated\ndocs/** -linguist-documentation\n",
+        )
+        .unwrap();
+        fs::write(root.join("src/proto/api.rs"), "pub struct Api;\n").unwrap();
+        fs::write(root.join("src/schema.rs"), "// @generated by diesel\n").unwrap();
+        fs::write(root.join("src/bundle.js"), "x".repeat(MAX_LINE_LENGTH + 1)).unwrap();
+        fs::write(root.join("src/main.rs"), "fn main() {}\n").unwrap();
+        // 読み込む範囲より後ろの長い行は見ない
+        let content = format!(
+            "{}{}\n",
+            "// text\n".repeat(CONTENT_PREFIX as usize / 8),
+            "x".repeat(MAX_LINE_LENGTH + 1)
+        );
+        fs::write(root.join("src/long.rs"), content).unwrap();
+        fs::write(root.join("docs/guide.md"), "# Guide\n").unwrap();
+
+        let classifier = Classifier::new();
+        let classify = |path: &str| classifier.classify(&root.join(path), Path::new(path));
+
+        assert_eq!(classify("src/proto/api.rs"), Some(FileClass::Generated));
+        assert_eq!(classify("src/schema.rs"), Some(FileClass::Generated));
+        assert_eq!(classify("src/bundle.js"), Some(FileClass::Generated));
+        assert_eq!(classify("src/main.rs"), None);
+        assert_eq!(classify("src/long.rs"), None);
+        assert_eq!(classify("docs/guide.md"), None);
+    }
+}
@@ -1,3 +1,4 @@
+use crate::git::Selection;
 use clap::Parser;
 
 /// プロンプトテンプレート内のファイル内容を展開し、LLMへの入力に適した形式に変換するCLIツール
@@ -14,10 +15,44 @@ pub struct Cli {
     ///
     /// You can expand the contents of all matching files in place using the following directives in the template:
     ///
-    /// - #ef <glob_pattern>: File selection using glob patterns
+    /// - #ef <glob_pattern>...: File selection using glob patterns (multiple patterns, quoting and {a,b} alternation are supported)
+    ///
+    /// - #ef <glob_pattern>... select=<mode>: Only expand files in the given git state (tracked-only, staged, modified, include-untracked; also works at the end of #efr)
+    ///
+    /// - #ef <glob_pattern>... include=<kinds>: Also expand generated, vendored or documentation files (lockfiles, *.min.js, dist/, vendor/, docs/, linguist-* in .gitattributes, ...), which wildcard patterns and #efr skip by default (kinds: generated, vendored, documentation, all)
+    ///
+    /// - #ef <path>:<start>-<end>: Embed only the given line range of a file (e.g. src/main.rs:10-40, src/main.rs:100-)
+    ///
+    /// - #ef git:<revision>:<path> (or #ef <path> rev=<revision>): Embed a file as of a git revision (the revision is appended to {filePath}, e.g. src/main.rs@HEAD~3)
+    ///
+    /// - #efregion <path>#<name>: Embed only the lines between `ef:begin <name>` and `ef:end <name>` comments
+    ///
+    /// - #efsym <path>::<symbol>: Embed only the definition of a function, type or class (Rust, TypeScript/JavaScript, Python)
+    ///
+    /// - #efrs crate::<path>::<Item> [tests=false]: Embed a Rust item with its impl blocks (or a whole module), following `mod` declarations from src/lib.rs or src/main.rs
+    ///
+    /// - #efmod <crate_root> [depth=N]: Embed the files of a Rust module tree, following `mod` declarations in declaration order
+    ///
+    /// - #efdeps <entry_file> [depth=N]: Embed a JS/TS or Python file and the project files it imports (tsconfig paths are supported; node_modules, the standard library and site-packages are skipped)
+    ///
+    /// - #eftree <dir> [depth=N] [size=true] [lines=true] [hidden=true]: Embed an ASCII tree of a directory (ignore patterns apply; binary and hidden files are skipped)
+    ///
+    /// - #efdiff [<rev-range>] [-- <pathspec>...] [context=N] [staged=true]: Embed a unified diff per file (working tree vs HEAD by default; requires git)
+    ///
+    /// - #efchanged [<base-ref>] [untracked=true]: Embed the current content of files added or modified since a git revision (HEAD by default; deleted files are reported as warnings)
+    ///
+    /// - #efcmd <command>: Embed the output and exit code of a shell command run in the template's directory (requires --allow-commands)
+    ///
+    /// - #efinclude <template_file>: Splice another template in place (resolved relative to the including file)
+    ///
+    /// - #efset <name> = <value>: Define a variable; `{{name}}` in text lines and directive arguments is replaced with its value (e.g. #ef src/{{module}}/*.rs)
+    ///
+    /// - #efif <condition> / #efelse / #efendif: Expand the enclosed lines only when the condition holds (name, name == value, name != value, env(NAME), exists(path), !condition)
     ///
     /// - #efr <regex_pattern>: File selection using regular expressions
     ///
+    /// - #efx <glob_pattern>: Exclude matching files from the following directives (or use `!pattern` inside #ef)
+    ///
     /// File path resolution follows these rules:
     ///
     /// - Relative paths: Resolved relative to the template file's directory
@@ -36,6 +71,42 @@ pub struct Cli {
     #[arg(short, long)]
     #[arg(help_heading = "Options")]
     pub debug: bool,
+
+    /// Define a template variable (overrides #efset in the template)
+    #[arg(short = 'D', long = "define", value_name = "NAME=VALUE")]
+    #[arg(value_parser = parse_define)]
+    #[arg(help_heading = "Options")]
+    pub defines: Vec<(String, String)>,
+
+    /// Do not skip files matched by .gitignore, .git/info/exclude, global git excludes or .efignore
+    #[arg(long)]
+    #[arg(help_heading = "Options")]
+    pub no_ignore: bool,
+
+    /// Only expand files in the given git state for #ef and #efr (override per directive with select=<mode>)
+    #[arg(long, value_enum, value_name = "MODE", default_value_t = Selection::All)]
+    #[arg(help_heading = "Options")]
+    pub select: Selection,
+
+    /// Allow #efcmd directives to run shell commands
+    #[arg(long)]
+    #[arg(help_heading = "Options")]
+    pub allow_commands: bool,
+
+    /// Timeout for each #efcmd command
+    #[arg(long, value_name = "SECONDS", default_value_t = 30)]
+    #[arg(help_heading = "Options")]
+    pub command_timeout: u64,
+}
+
+fn parse_define(define: &str) -> Result<(String, String), String> {
+    let (name, value) = define
+        .split_once('=')
+        .ok_or_else(|| format!("expected NAME=VALUE, got `{define}`"))?;
+    if !crate::template::is_variable_name(name) {
+        return Err(format!("invalid variable name `{name}`"));
+    }
+    Ok((name.to_string(), value.to_string()))
 }
 pub fn parse_cli() -> Cli {
     Cli::parse()
@@ -67,4 +138,58 @@ mod tests {
         assert!(!cli.debug);
         assert_eq!(cli.template_path, "template.txt");
     }
+
+    #[test]
+    fn test_cli_command_options() {
+        let cli = Cli::parse_from(vec!["ef", "template.txt"]);
+        assert!(!cli.allow_commands);
+        assert_eq!(cli.command_timeout, 30);
+
+        let args = vec![
+            "ef",
+            "template.txt",
+            "--allow-commands",
+            "--command-timeout",
+            "5",
+        ];
+        let cli = Cli::parse_from(args);
+        assert!(cli.allow_commands);
+        assert_eq!(cli.command_timeout, 5);
+    }
+
+    #[test]
+    fn test_cli_select() {
+        let cli = Cli::parse_from(vec!["ef", "template.txt"]);
+        assert_eq!(cli.select, Selection::All);
+
+        let cli = Cli::parse_from(vec!["ef", "template.txt", "--select", "tracked-only"]);
+        assert_eq!(cli.select, Selection::TrackedOnly);
+        assert!(Cli::try_parse_from(vec!["ef", "template.txt", "--select", "tracked"]).is_err());
+    }
+
+    #[test]
+    fn test_cli_defines() {
+        let args = vec![
+            "ef",
+            "template.txt",
+            "-D",
+            "module=parser",
+            "--define",
+            "empty=",
+        ];
+        let cli = Cli::parse_from(args);
+        assert_eq!(
+            cli.defines,
+            vec![
+                ("module".to_string(), "parser".to_string()),
+                ("empty".to_string(), String::new()),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_cli_invalid_define() {
+        assert!(Cli::try_parse_from(vec!["ef", "template.txt", "-D", "module"]).is_err());
+        assert!(Cli::try_parse_from(vec!["ef", "template.txt", "-D", "1st=x"]).is_err());
+    }
 }
@@ -0,0 +1,210 @@
+use std::io::{self, Read};
+use std::path::Path;
+use std::process::{Child, Command, ExitStatus, Stdio};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// 終了を確認する間隔
+const POLL_INTERVAL: Duration = Duration::from_millis(10);
+
+pub struct CommandOutput {
+    pub stdout: String,
+    pub stderr: String,
+    /// タイムアウトした場合は`None`
+    pub status: Option<ExitStatus>,
+}
+
+impl CommandOutput {
+    /// 標準出力と標準エラー出力をこの順に連結したもの
+    pub fn combined(&self) -> String {
+        [self.stdout.as_str(), self.stderr.as_str()]
+            .into_iter()
+            .filter(|output| !output.is_empty())
+            .collect::<Vec<_>>()
+            .join("\n")
+    }
+
+    /// `{exitCode}`として表示する値（タイムアウトは`timeout`、シグナルでの終了は`signal`）
+    pub fn exit_code(&self) -> String {
+        match self.status {
+            Some(status) => status
+                .code()
+                .map_or_else(|| "signal".to_string(), |code| code.to_string()),
+            None => "timeout".to_string(),
+        }
+    }
+}
+
+/// シェル経由でコマンドを実行し、出力を取り込む
+///
+/// `timeout`を過ぎたらプロセスを終了させ、それまでに得られた出力を返す。
+pub fn run(command: &str, dir: &Path, timeout: Duration) -> io::Result<CommandOutput> {
+    let mut child = shell(command)
+        .current_dir(dir)
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()?;
+
+    let stdout = capture(child.stdout.take());
+    let stderr = capture(child.stderr.take());
+    let status = wait(&mut child, timeout)?;
+
+    // タイムアウト時は孫プロセスがパイプを開いたままのことがあるので、読み取りの終了を待たない
+    let (stdout, stderr) = match status {
+        Some(_) => (stdout.join(), stderr.join()),
+        None => (stdout.snapshot(), stderr.snapshot()),
+    };
+
+    Ok(CommandOutput {
+        stdout,
+        stderr,
+        status,
+    })
+}
+
+#[cfg(windows)]
+fn shell(command: &str) -> Command {
+    let mut shell = Command::new("cmd");
+    shell.arg("/C").arg(command);
+    shell
+}
+
+#[cfg(not(windows))]
+fn shell(command: &str) -> Command {
+    use std::os::unix::process::CommandExt;
+
+    let mut shell = Command::new("sh");
+    // タイムアウト時にシェルから起動されたコマンドもまとめて終了させるため、別のプロセスグループにする
+    shell.arg("-c").arg(command).process_group(0);
+    shell
+}
+
+fn wait(child: &mut Child, timeout: Duration) -> io::Result<Option<ExitStatus>> {
+    let deadline = Instant::now() + timeout;
+    loop {
+        if let Some(status) = child.try_wait()? {
+            return Ok(Some(status));
+        }
+        if Instant::now() >= deadline {
+            kill(child)?;
+            child.wait()?;
+            return Ok(None);
+        }
+        thread::sleep(POLL_INTERVAL);
+    }
+}
+
+#[cfg(unix)]
+fn kill(child: &mut Child) -> io::Result<()> {
+    let group = child.id() as libc::pid_t;
+    // SAFETY: シグナルを送るだけで、メモリには触れない
+    if unsafe { libc::kill(-group, libc::SIGKILL) } == 0 {
+        return Ok(());
+    }
+
+    let error = io::Error::last_os_error();
+    match error.raw_os_error() {
+        // すでにすべて終了している
+        Some(libc::ESRCH) => Ok(()),
+        _ => Err(error),
+    }
+}
+
+#[cfg(not(unix))]
+fn kill(child: &mut Child) -> io::Result<()> {
+    child.kill()
+}
+
+/// パイプの内容を別スレッドで読み続けるバッファ
+struct Capture {
+    buffer: Arc<Mutex<Vec<u8>>>,
+    reader: Option<thread::JoinHandle<()>>,
+}
+
+impl Capture {
+    fn join(mut self) -> String {
+        if let Some(reader) = self.reader.take() {
+            let _ = reader.join();
+        }
+        self.snapshot()
+    }
+
+    fn snapshot(&self) -> String {
+        let buffer = self.buffer.lock().unwrap_or_else(|e| e.into_inner());
+        String::from_utf8_lossy(&buffer).trim_end().to_string()
+    }
+}
+
+fn capture<R: Read + Send + 'static>(pipe: Option<R>) -> Capture {
+    let buffer = Arc::new(Mutex::new(Vec::new()));
+    let reader = pipe.map(|mut pipe| {
+        let buffer = Arc::clone(&buffer);
+        thread::spawn(move || {
+            let mut chunk = [0; 8192];
+            while let Ok(read @ 1..) = pipe.read(&mut chunk) {
+                buffer
+                    .lock()
+                    .unwrap_or_else(|e| e.into_inner())
+                    .extend_from_slice(&chunk[..read]);
+            }
+        })
+    });
+    Capture { buffer, reader }
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_run_captures_output_and_status() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::write(dir.path().join("marker.txt"), "").unwrap();
+
+        let output = run(
+            "ls; echo failure >&2; exit 3",
+            dir.path(),
+            Duration::from_secs(10),
+        )
+        .unwrap();
+        assert_eq!(output.stdout, "marker.txt");
+        assert_eq!(output.stderr, "failure");
+        assert_eq!(output.combined(), "marker.txt\nfailure");
+        assert_eq!(output.exit_code(), "3");
+        assert!(!output.status.unwrap().success());
+    }
+
+    /// プロセスが実行中か（終了して回収されていないものは含めない）
+    fn is_running(pid: &str) -> bool {
+        let output = Command::new("ps")
+            .args(["-o", "stat=", "-p", pid])
+            .output()
+            .unwrap();
+        let stat = String::from_utf8_lossy(&output.stdout);
+        !stat.trim().is_empty() && !stat.trim().starts_with('Z')
+    }
+
+    #[test]
+    fn test_run_timeout() {
+        let dir = tempfile::tempdir().unwrap();
+        let output = run(
+            "echo started; sleep 5 & echo $! > sleep.pid; wait",
+            dir.path(),
+            Duration::from_millis(300),
+        )
+        .unwrap();
+        assert!(output.status.is_none());
+        assert_eq!(output.exit_code(), "timeout");
+
+        // シェルから起動されたコマンドも終了している
+        let pid = std::fs::read_to_string(dir.path().join("sleep.pid")).unwrap();
+        let pid = pid.trim();
+        let deadline = Instant::now() + Duration::from_secs(2);
+        while is_running(pid) && Instant::now() < deadline {
+            thread::sleep(POLL_INTERVAL);
+        }
+        assert!(!is_running(pid));
+    }
+}
@@ -0,0 +1,205 @@
+/// 言語ごとの行コメントの開始記号
+///
+/// 言語名は`language_mappings.ron`の値（小文字）に合わせる。
+const COMMENT_PREFIXES: &[(&[&str], &[&str])] = &[
+    (
+        &[
+            "rust",
+            "c",
+            "c++",
+            "c#",
+            "objective-c",
+            "objective-c++",
+            "java",
+            "kotlin",
+            "scala",
+            "groovy",
+            "go",
+            "swift",
+            "dart",
+            "javascript",
+            "typescript",
+            "tsx",
+            "jsx",
+            "json5",
+            "zig",
+            "protocol buffer",
+            "glsl",
+            "hlsl",
+            "cuda",
+            "solidity",
+            "d",
+            "haxe",
+            "f#",
+            "v",
+            "odin",
+            "gleam",
+        ],
+        &["//", "/*", "*"],
+    ),
+    (
+        &["css", "scss", "less", "sass", "stylus", "postcss"],
+        &["/*", "//", "*"],
+    ),
+    (&["php", "hack"], &["//", "#", "/*", "*"]),
+    (
+        &[
+            "python",
+            "ruby",
+            "shell",
+            "tcsh",
+            "fish",
+            "nushell",
+            "perl",
+            "raku",
+            "r",
+            "julia",
+            "yaml",
+            "toml",
+            "makefile",
+            "just",
+            "dockerfile",
+            "cmake",
+            "starlark",
+            "elixir",
+            "nim",
+            "crystal",
+            "powershell",
+            "tcl",
+            "awk",
+            "sed",
+            "graphql",
+            "nix",
+            "hcl",
+            "meson",
+            "gn",
+            "nginx",
+            "apacheconf",
+            "dotenv",
+            "editorconfig",
+            "gitignore",
+            "snakemake",
+            "cython",
+            "coffeescript",
+            "terraform template",
+        ],
+        &["#"],
+    ),
+    (
+        &[
+            "sql",
+            "plsql",
+            "plpgsql",
+            "lua",
+            "luau",
+            "haskell",
+            "elm",
+            "ada",
+            "purescript",
+            "idris",
+            "agda",
+            "vhdl",
+            "dhall",
+        ],
+        &["--", "{-"],
+    ),
+    (
+        &[
+            "common lisp",
+            "emacs lisp",
+            "clojure",
+            "scheme",
+            "racket",
+            "fennel",
+            "janet",
+            "hy",
+            "ini",
+            "assembly",
+        ],
+        &[";"],
+    ),
+    (
+        &["erlang", "tex", "matlab", "prolog", "mercury", "postscript"],
+        &["%"],
+    ),
+    (
+        &[
+            "html", "xml", "markdown", "vue", "svelte", "svg", "astro", "mdx",
+        ],
+        &["<!--", "//", "/*"],
+    ),
+    (
+        &["ocaml", "coq", "pascal", "mathematica"],
+        &["(*", "//", "{"],
+    ),
+    (&["batchfile"], &["rem", "REM", "::"]),
+    (&["vim script", "vim snippet"], &["\""]),
+    (&["visual basic .net", "vba", "vbscript"], &["'"]),
+    (&["fortran", "fortran free form"], &["!"]),
+];
+
+/// 言語が不明な場合に試す行コメントの開始記号
+const FALLBACK_PREFIXES: &[&str] = &["//", "/*", "*", "#", "--", ";", "%", "<!--", "(*", "'"];
+
+/// 言語の行コメントの開始記号を返す
+///
+/// 言語が不明、または表にない場合は、よく使われる記号をすべて返す。
+pub fn prefixes(language: Option<&str>) -> &'static [&'static str] {
+    language
+        .and_then(|language| {
+            COMMENT_PREFIXES
+                .iter()
+                .find(|(languages, _)| languages.contains(&language))
+        })
+        .map(|(_, prefixes)| *prefixes)
+        .unwrap_or(FALLBACK_PREFIXES)
+}
+
+/// 行がコメントであれば、コメント記号を除いた本文を返す
+///
+/// `-->`や`*/`のような閉じ記号も取り除く。
+pub fn strip<'a>(line: &'a str, prefixes: &[&str]) -> Option<&'a str> {
+    let line = line.trim();
+    let body = prefixes
+        .iter()
+        .find_map(|prefix| line.strip_prefix(prefix))?;
+
+    // `///`や`##`のように記号を重ねた場合
+    let body = body.trim_start_matches(|c: char| "/#*-;%!".contains(c));
+    let body = ["-->", "*/", "-}", "*)", "}"]
+        .iter()
+        .find_map(|suffix| body.trim_end().strip_suffix(suffix))
+        .unwrap_or(body);
+
+    Some(body.trim())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_prefixes() {
+        assert_eq!(prefixes(Some("python")), &["#"]);
+        assert!(prefixes(Some("rust")).contains(&"//"));
+        assert_eq!(prefixes(None), FALLBACK_PREFIXES);
+        assert_eq!(prefixes(Some("unknown")), FALLBACK_PREFIXES);
+    }
+
+    #[test]
+    fn test_strip() {
+        let prefixes = prefixes(None);
+        assert_eq!(
+            strip("  // ef:begin setup", prefixes),
+            Some("ef:begin setup")
+        );
+        assert_eq!(strip("/// note", prefixes), Some("note"));
+        assert_eq!(
+            strip("<!-- ef:end setup -->", prefixes),
+            Some("ef:end setup")
+        );
+        assert_eq!(strip("/* ef:end setup */", prefixes), Some("ef:end setup"));
+        assert_eq!(strip("let x = 1; // no", prefixes), None);
+        assert_eq!(strip("# heading", &["//"]), None);
+    }
+}
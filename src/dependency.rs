@@ -0,0 +1,724 @@
+use crate::eftemplate;
+use once_cell::sync::Lazy;
+use regex::Regex;
+use serde::Deserialize;
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Component, Path, PathBuf};
+
+/// 拡張子を省略したimportを解決する際に試す拡張子（優先順）
+const SCRIPT_EXTENSIONS: &[&str] = &["ts", "tsx", "js", "jsx", "mjs", "cjs"];
+
+static SCRIPT_IMPORTS: Lazy<Vec<Regex>> = Lazy::new(|| {
+    [
+        // import x from "..." / import { x } from "..." / import type { X } from "..."
+        r#"\bimport\s+(?:type\s+)?[^'";]*?\bfrom\s*['"]([^'"]+)['"]"#,
+        // import "..."（副作用のみ）
+        r#"\bimport\s*['"]([^'"]+)['"]"#,
+        // export { x } from "..." / export * from "..."
+        r#"\bexport\s+(?:type\s+)?[^'";]*?\bfrom\s*['"]([^'"]+)['"]"#,
+        // require("...") / import("...")
+        r#"\b(?:require|import)\s*\(\s*['"]([^'"]+)['"]\s*\)"#,
+    ]
+    .iter()
+    .map(|pattern| Regex::new(pattern).unwrap())
+    .collect()
+});
+
+/// Pythonのプロジェクトのルートを示すファイル
+const PYTHON_PROJECT_FILES: &[&str] = &["pyproject.toml", "setup.py", "setup.cfg"];
+
+/// これらのディレクトリ以下のPythonのモジュールはサードパーティとみなす
+const PYTHON_EXTERNAL_DIRS: &[&str] = &["site-packages", "dist-packages", ".venv", "venv"];
+
+static PYTHON_IMPORT: Lazy<Regex> = Lazy::new(|| Regex::new(r"^\s*import\s+(.+)$").unwrap());
+
+static PYTHON_FROM_IMPORT: Lazy<Regex> =
+    Lazy::new(|| Regex::new(r"^\s*from\s+(\.*[\w.]*)\s+import\s+(.+)$").unwrap());
+
+/// import元の言語
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum SourceKind {
+    /// TypeScript / JavaScript
+    Script,
+    Python,
+}
+
+impl SourceKind {
+    pub fn from_path(path: &Path) -> Option<Self> {
+        let extension = path.extension()?.to_str()?;
+        if SCRIPT_EXTENSIONS.contains(&extension) {
+            Some(Self::Script)
+        } else if matches!(extension, "py" | "pyi") {
+            Some(Self::Python)
+        } else {
+            None
+        }
+    }
+}
+
+/// importの解決結果
+#[derive(Debug, PartialEq)]
+pub enum Resolution {
+    /// プロジェクト内のファイル
+    Local(PathBuf),
+    /// node_modulesのパッケージなど、展開の対象外
+    External,
+    /// 相対パスやエイリアスで指定されているが、ファイルが見つからない
+    NotFound,
+}
+
+/// ファイルに書かれたimportの指定（`./foo`、`@/lib/api`など）を出現順に返す
+pub fn script_imports(content: &str) -> Vec<String> {
+    let mut imports = SCRIPT_IMPORTS
+        .iter()
+        .flat_map(|regex| {
+            regex
+                .captures_iter(content)
+                .map(|captures| (captures.get(1).unwrap().start(), captures[1].to_string()))
+        })
+        .collect::<Vec<_>>();
+    imports.sort();
+
+    let mut result = Vec::<String>::new();
+    for (_, specifier) in imports {
+        if !result.contains(&specifier) {
+            result.push(specifier);
+        }
+    }
+    result
+}
+
+/// tsconfig.json（またはjsconfig.json）の`baseUrl`と`paths`
+#[derive(Debug, Default)]
+pub struct TsConfig {
+    base_url: Option<PathBuf>,
+    /// `@/*` → `["src/*"]`のようなエイリアス（`baseUrl`からの相対パス）
+    paths: Vec<(String, Vec<String>)>,
+}
+
+#[derive(Deserialize, Default)]
+#[serde(default, rename_all = "camelCase")]
+struct RawTsConfig {
+    compiler_options: RawCompilerOptions,
+}
+
+#[derive(Deserialize, Default)]
+#[serde(default, rename_all = "camelCase")]
+struct RawCompilerOptions {
+    base_url: Option<String>,
+    paths: BTreeMap<String, Vec<String>>,
+}
+
+impl TsConfig {
+    /// `start_path`から上位に向かってtsconfig.json、jsconfig.jsonを探して読み込む
+    ///
+    /// 見つからない場合や読み込めない場合はエイリアスなしとして扱う。
+    pub fn find_and_load<P: AsRef<Path>>(start_path: P) -> Self {
+        ["tsconfig.json", "jsconfig.json"]
+            .iter()
+            .find_map(|file_name| eftemplate::find_upwards(start_path.as_ref(), file_name).ok()?)
+            .and_then(|path| Self::from_file(&path))
+            .unwrap_or_default()
+    }
+
+    fn from_file(path: &Path) -> Option<Self> {
+        let content = fs::read_to_string(path).ok()?;
+        let raw: RawTsConfig = serde_json::from_str(&strip_json_comments(&content)).ok()?;
+        let dir = path.parent()?;
+
+        let options = raw.compiler_options;
+        let base_url = options.base_url.map(|base_url| dir.join(base_url));
+        // `paths`は`baseUrl`がなければtsconfig.jsonのディレクトリからの相対パス
+        let paths_base = base_url.clone().unwrap_or_else(|| dir.to_path_buf());
+        let mut paths = options
+            .paths
+            .into_iter()
+            .map(|(pattern, targets)| {
+                let targets = targets
+                    .iter()
+                    .map(|target| paths_base.join(target).to_string_lossy().into_owned())
+                    .collect();
+                (pattern, targets)
+            })
+            .collect::<Vec<_>>();
+        // より具体的な（`*`の前が長い）パターンを優先する
+        paths.sort_by_key(|(pattern, _)| {
+            std::cmp::Reverse(pattern.find('*').unwrap_or(pattern.len()))
+        });
+
+        Some(Self { base_url, paths })
+    }
+
+    fn has_alias_for(&self, specifier: &str) -> bool {
+        self.paths
+            .iter()
+            .any(|(pattern, _)| match pattern.split_once('*') {
+                Some((prefix, suffix)) => {
+                    specifier.starts_with(prefix) && specifier.ends_with(suffix)
+                }
+                None => specifier == pattern,
+            })
+    }
+
+    /// エイリアスを展開した候補のパスを返す
+    fn expand_alias(&self, specifier: &str) -> Vec<PathBuf> {
+        let mut candidates = Vec::new();
+
+        for (pattern, targets) in &self.paths {
+            let captured = match pattern.split_once('*') {
+                Some((prefix, suffix)) => specifier
+                    .strip_prefix(prefix)
+                    .and_then(|rest| rest.strip_suffix(suffix)),
+                None => (specifier == pattern).then_some(""),
+            };
+            if let Some(captured) = captured {
+                candidates.extend(
+                    targets
+                        .iter()
+                        .map(|target| PathBuf::from(target.replacen('*', captured, 1))),
+                );
+            }
+        }
+
+        if let Some(base_url) = &self.base_url {
+            candidates.push(base_url.join(specifier));
+        }
+
+        candidates
+    }
+}
+
+/// tsconfig.jsonに書けるコメントと末尾のカンマを取り除く
+fn strip_json_comments(content: &str) -> String {
+    let mut result = String::with_capacity(content.len());
+    let mut chars = content.chars().peekable();
+    let mut in_string = false;
+
+    while let Some(c) = chars.next() {
+        if in_string {
+            result.push(c);
+            match c {
+                '\\' => result.extend(chars.next()),
+                '"' => in_string = false,
+                _ => {}
+            }
+            continue;
+        }
+
+        match (c, chars.peek()) {
+            ('"', _) => {
+                in_string = true;
+                result.push(c);
+            }
+            ('/', Some('/')) => {
+                for c in chars.by_ref() {
+                    if c == '\n' {
+                        result.push(c);
+                        break;
+                    }
+                }
+            }
+            ('/', Some('*')) => {
+                chars.next();
+                let mut previous = ' ';
+                for c in chars.by_ref() {
+                    if previous == '*' && c == '/' {
+                        break;
+                    }
+                    previous = c;
+                }
+            }
+            _ => result.push(c),
+        }
+    }
+
+    static TRAILING_COMMA: Lazy<Regex> = Lazy::new(|| Regex::new(r",(\s*[}\]])").unwrap());
+    TRAILING_COMMA.replace_all(&result, "$1").into_owned()
+}
+
+/// JS/TSのimportの指定を、importしているファイルからの位置として解決する
+pub fn resolve_script_import(from: &Path, specifier: &str, tsconfig: &TsConfig) -> Resolution {
+    // `"."`・`".."`はそのディレクトリのindexファイル
+    let is_relative = matches!(specifier, "." | "..")
+        || specifier.starts_with("./")
+        || specifier.starts_with("../");
+    let candidates = if is_relative {
+        let dir = from.parent().unwrap_or_else(|| Path::new("."));
+        vec![dir.join(specifier)]
+    } else {
+        tsconfig.expand_alias(specifier)
+    };
+
+    let found = candidates
+        .iter()
+        .map(|candidate| normalize(candidate))
+        .find_map(|candidate| {
+            script_file_candidates(&candidate)
+                .into_iter()
+                .find(|path| path.is_file())
+        });
+
+    match found {
+        Some(path) if is_in_node_modules(&path) => Resolution::External,
+        Some(path) => Resolution::Local(path),
+        // baseUrlからも見つからない非相対パスはパッケージとみなす
+        None if is_relative || tsconfig.has_alias_for(specifier) => Resolution::NotFound,
+        None => Resolution::External,
+    }
+}
+
+/// `./foo`から、`./foo`、`./foo.ts`、`./foo/index.ts`などの候補を作る
+fn script_file_candidates(path: &Path) -> Vec<PathBuf> {
+    let mut candidates = vec![path.to_path_buf()];
+
+    // ESM形式で`./foo.js`と書かれたTypeScriptのファイル
+    if let Some(extension @ ("js" | "jsx" | "mjs" | "cjs")) =
+        path.extension().and_then(|extension| extension.to_str())
+    {
+        let replacements: &[&str] = match extension {
+            "js" => &["ts", "tsx"],
+            "jsx" => &["tsx"],
+            "mjs" => &["mts"],
+            _ => &["cts"],
+        };
+        candidates.extend(
+            replacements
+                .iter()
+                .map(|replacement| path.with_extension(replacement)),
+        );
+    }
+
+    let file_name = path.file_name().unwrap_or_default().to_string_lossy();
+    candidates.extend(
+        SCRIPT_EXTENSIONS
+            .iter()
+            .map(|extension| path.with_file_name(format!("{}.{}", file_name, extension))),
+    );
+    candidates.extend(
+        SCRIPT_EXTENSIONS
+            .iter()
+            .map(|extension| path.join(format!("index.{}", extension))),
+    );
+
+    candidates
+}
+
+fn is_in_node_modules(path: &Path) -> bool {
+    path.components()
+        .any(|component| component.as_os_str() == "node_modules")
+}
+
+/// Pythonの`import a.b`、`from .y import z`
+#[derive(Debug, PartialEq)]
+pub struct PythonImport {
+    /// 先頭の`.`を含むモジュール名（`a.b`、`.y`、`..`など）
+    pub module: String,
+    /// `from ... import`でimportした名前（サブモジュールの可能性がある）
+    pub names: Vec<String>,
+}
+
+/// Pythonのファイルに書かれたimportを出現順に返す
+pub fn python_imports(content: &str) -> Vec<PythonImport> {
+    let mut imports = Vec::new();
+
+    for line in python_logical_lines(content) {
+        if let Some(captures) = PYTHON_FROM_IMPORT.captures(&line) {
+            let names = captures[2]
+                .split(',')
+                .map(|name| name.trim().trim_matches(|c| c == '(' || c == ')').trim())
+                .filter_map(|name| name.split_whitespace().next())
+                .filter(|name| *name != "*")
+                .map(str::to_string)
+                .collect();
+            imports.push(PythonImport {
+                module: captures[1].to_string(),
+                names,
+            });
+        } else if let Some(captures) = PYTHON_IMPORT.captures(&line) {
+            imports.extend(captures[1].split(',').filter_map(|module| {
+                let module = module.split_whitespace().next()?;
+                Some(PythonImport {
+                    module: module.to_string(),
+                    names: Vec::new(),
+                })
+            }));
+        }
+    }
+
+    imports
+}
+
+/// 括弧や`\`による継続行をつなげ、コメントと複数行の文字列を除いた行を返す
+fn python_logical_lines(content: &str) -> Vec<String> {
+    let mut lines = Vec::new();
+    let mut current = String::new();
+    let mut depth = 0i32;
+    let mut in_docstring = None;
+
+    for line in content.lines() {
+        if let Some(quote) = in_docstring {
+            if line.contains(quote) {
+                in_docstring = None;
+            }
+            continue;
+        }
+
+        let trimmed = line.trim_start();
+        if let Some(quote) = ["\"\"\"", "'''"]
+            .into_iter()
+            .find(|quote| trimmed.starts_with(quote))
+        {
+            if !trimmed[3..].contains(quote) {
+                in_docstring = Some(quote);
+            }
+            continue;
+        }
+
+        let code = match line.find('#') {
+            Some(index) if !line[..index].contains(['"', '\'']) => &line[..index],
+            _ => line,
+        };
+
+        depth += code.matches('(').count() as i32 - code.matches(')').count() as i32;
+        let continued = code.trim_end().ends_with('\\');
+        current.push_str(code.trim_end().trim_end_matches('\\'));
+        current.push(' ');
+
+        if depth <= 0 && !continued {
+            lines.push(std::mem::take(&mut current));
+            depth = 0;
+        }
+    }
+
+    if !current.is_empty() {
+        lines.push(current);
+    }
+    lines
+}
+
+/// Pythonのプロジェクトで、絶対importを探すディレクトリを返す
+///
+/// pyproject.toml、setup.py、setup.cfgのあるディレクトリ（とその`src/`）をプロジェクトのルートとする。
+/// パッケージ（`__init__.py`のあるディレクトリ）に属さないスクリプトは、自身のディレクトリも探す。
+pub fn python_roots(from: &Path, default_root: &Path) -> Vec<PathBuf> {
+    let project_root = PYTHON_PROJECT_FILES
+        .iter()
+        .find_map(|file_name| eftemplate::find_upwards(from, file_name).ok()?)
+        .and_then(|path| Some(path.parent()?.to_path_buf()))
+        .unwrap_or_else(|| default_root.to_path_buf());
+
+    let mut roots = vec![project_root.join("src"), project_root];
+    let dir = from.parent().unwrap_or_else(|| Path::new("."));
+    if !dir.join("__init__.py").is_file() {
+        roots.insert(0, dir.to_path_buf());
+    }
+    roots
+}
+
+/// Pythonのimportを、プロジェクト内のファイルとして解決する
+///
+/// `from pkg import name`の`name`がサブモジュールならそのファイルを、そうでなければ`pkg`自身を返す。
+pub fn resolve_python_import(
+    from: &Path,
+    import: &PythonImport,
+    roots: &[PathBuf],
+) -> Vec<Resolution> {
+    let dots = import.module.chars().take_while(|&c| c == '.').count();
+    let module_path = import.module[dots..]
+        .split('.')
+        .filter(|part| !part.is_empty())
+        .collect::<PathBuf>();
+
+    let bases = if dots > 0 {
+        let mut base = from
+            .parent()
+            .unwrap_or_else(|| Path::new("."))
+            .to_path_buf();
+        for _ in 1..dots {
+            base.pop();
+        }
+        vec![base.join(&module_path)]
+    } else {
+        roots.iter().map(|root| root.join(&module_path)).collect()
+    };
+
+    let mut resolutions = Vec::new();
+    let mut module_needed = import.names.is_empty();
+    for name in &import.names {
+        match bases
+            .iter()
+            .find_map(|base| python_module_file(&base.join(name)))
+        {
+            Some(path) => resolutions.push(python_resolution(path)),
+            None => module_needed = true,
+        }
+    }
+
+    if module_needed {
+        let module = if module_path.as_os_str().is_empty() {
+            // `from . import name`の`name`がモジュールでなければ、パッケージの`__init__.py`
+            bases
+                .iter()
+                .map(|base| base.join("__init__.py"))
+                .find(|path| path.is_file())
+        } else {
+            bases.iter().find_map(|base| python_module_file(base))
+        };
+
+        resolutions.push(match module {
+            Some(path) => python_resolution(path),
+            None if dots > 0 => Resolution::NotFound,
+            // 標準ライブラリやインストール済みのパッケージ
+            None => Resolution::External,
+        });
+    }
+
+    resolutions
+}
+
+/// `a/b`に対応する`a/b.py`、`a/b/__init__.py`を探す
+fn python_module_file(path: &Path) -> Option<PathBuf> {
+    let file_name = path.file_name()?.to_string_lossy();
+    [
+        path.with_file_name(format!("{}.py", file_name)),
+        path.with_file_name(format!("{}.pyi", file_name)),
+        path.join("__init__.py"),
+    ]
+    .into_iter()
+    .map(|path| normalize(&path))
+    .find(|path| path.is_file())
+}
+
+fn python_resolution(path: PathBuf) -> Resolution {
+    let is_external = path.components().any(|component| {
+        PYTHON_EXTERNAL_DIRS
+            .iter()
+            .any(|dir| component.as_os_str() == *dir)
+    });
+
+    if is_external {
+        Resolution::External
+    } else {
+        Resolution::Local(path)
+    }
+}
+
+/// `..`や`.`を取り除く（シンボリックリンクは解決しない）
+fn normalize(path: &Path) -> PathBuf {
+    let mut result = PathBuf::new();
+    for component in path.components() {
+        match component {
+            Component::CurDir => {}
+            Component::ParentDir => {
+                result.pop();
+            }
+            component => result.push(component),
+        }
+    }
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_script_imports() {
+        let content = r#"import React from "react";
+import { TaskItem } from './TaskItem';
+import type { Task } from "../types";
+import {
+  useTasks,
+  useFilter,
+} from "../hooks/useTasks";
+import "./styles.css";
+export * from "./utils";
+export { helper } from './helper';
+const legacy = require("./legacy");
+const lazy = import("./Lazy");
+"#;
+
+        assert_eq!(
+            script_imports(content),
+            vec![
+                "react",
+                "./TaskItem",
+                "../types",
+                "../hooks/useTasks",
+                "./styles.css",
+                "./utils",
+                "./helper",
+                "./legacy",
+                "./Lazy",
+            ]
+        );
+    }
+
+    fn setup() -> TempDir {
+        let dir = TempDir::new().unwrap();
+        let files = [
+            (
+                "tsconfig.json",
+                "{\n  // comment\n  \"compilerOptions\": {\n    \"baseUrl\": \".\",\n    \"paths\": { \"@/*\": [\"src/*\"], },\n  },\n}\n",
+            ),
+            ("src/components/TaskList.tsx", ""),
+            ("src/components/TaskItem.tsx", ""),
+            ("src/hooks/index.ts", ""),
+            ("src/components/index.ts", ""),
+            ("src/index.ts", ""),
+            ("src/lib/esm.ts", ""),
+            ("node_modules/pkg/index.js", ""),
+        ];
+        for (path, content) in files {
+            let path = dir.path().join(path);
+            fs::create_dir_all(path.parent().unwrap()).unwrap();
+            fs::write(path, content).unwrap();
+        }
+        dir
+    }
+
+    #[test]
+    fn test_python_imports() {
+        let content = r#""""Module docstring
+import fake
+"""
+import os, sys
+import app.models as models
+from . import utils
+from ..core.config import (
+    Settings,  # comment
+    load,
+)
+from app.services import user_service, \\
+    order_service
+
+def f():
+    from .lazy import thing
+"#;
+
+        let imports = python_imports(content);
+        let summary = imports
+            .iter()
+            .map(|import| format!("{} {:?}", import.module, import.names))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            summary,
+            vec![
+                "os []",
+                "sys []",
+                "app.models []",
+                ". [\"utils\"]",
+                "..core.config [\"Settings\", \"load\"]",
+                "app.services [\"user_service\", \"order_service\"]",
+                ".lazy [\"thing\"]",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_resolve_python_import() {
+        let dir = TempDir::new().unwrap();
+        let files = [
+            "pyproject.toml",
+            "src/app/__init__.py",
+            "src/app/main.py",
+            "src/app/models.py",
+            "src/app/services/__init__.py",
+            "src/app/services/user_service.py",
+            ".venv/lib/python3.12/site-packages/requests/__init__.py",
+        ];
+        for path in files {
+            let path = dir.path().join(path);
+            fs::create_dir_all(path.parent().unwrap()).unwrap();
+            fs::write(path, "").unwrap();
+        }
+
+        let root = dir.path().canonicalize().unwrap();
+        let from = root.join("src/app/main.py");
+        let roots = python_roots(&from, &root);
+        let resolve = |module: &str, names: &[&str]| {
+            let import = PythonImport {
+                module: module.to_string(),
+                names: names.iter().map(|name| name.to_string()).collect(),
+            };
+            resolve_python_import(&from, &import, &roots)
+        };
+
+        assert_eq!(
+            resolve("app.models", &[]),
+            vec![Resolution::Local(root.join("src/app/models.py"))]
+        );
+        assert_eq!(
+            resolve("app.services", &["user_service", "helper"]),
+            vec![
+                Resolution::Local(root.join("src/app/services/user_service.py")),
+                Resolution::Local(root.join("src/app/services/__init__.py")),
+            ]
+        );
+        assert_eq!(
+            resolve(".", &["models"]),
+            vec![Resolution::Local(root.join("src/app/models.py"))]
+        );
+        assert_eq!(
+            resolve(".", &["VERSION"]),
+            vec![Resolution::Local(root.join("src/app/__init__.py"))]
+        );
+        assert_eq!(resolve("os", &[]), vec![Resolution::External]);
+        assert_eq!(resolve(".missing", &["x"]), vec![Resolution::NotFound]);
+    }
+
+    #[test]
+    fn test_resolve_script_import() {
+        let dir = setup();
+        let root = dir.path().canonicalize().unwrap();
+        let from = root.join("src/components/TaskList.tsx");
+        let tsconfig = TsConfig::find_and_load(&from);
+
+        assert_eq!(
+            resolve_script_import(&from, "./TaskItem", &tsconfig),
+            Resolution::Local(root.join("src/components/TaskItem.tsx"))
+        );
+        assert_eq!(
+            resolve_script_import(&from, "../hooks", &tsconfig),
+            Resolution::Local(root.join("src/hooks/index.ts"))
+        );
+        assert_eq!(
+            resolve_script_import(&from, ".", &tsconfig),
+            Resolution::Local(root.join("src/components/index.ts"))
+        );
+        assert_eq!(
+            resolve_script_import(&from, "..", &tsconfig),
+            Resolution::Local(root.join("src/index.ts"))
+        );
+        assert_eq!(
+            resolve_script_import(&from, "../lib/esm.js", &tsconfig),
+            Resolution::Local(root.join("src/lib/esm.ts"))
+        );
+        assert_eq!(
+            resolve_script_import(&from, "@/hooks", &tsconfig),
+            Resolution::Local(root.join("src/hooks/index.ts"))
+        );
+        assert_eq!(
+            resolve_script_import(&from, "src/components/TaskItem", &tsconfig),
+            Resolution::Local(root.join("src/components/TaskItem.tsx"))
+        );
+        assert_eq!(
+            resolve_script_import(&from, "react", &tsconfig),
+            Resolution::External
+        );
+        assert_eq!(
+            resolve_script_import(&from, "node_modules/pkg", &tsconfig),
+            Resolution::External
+        );
+        assert_eq!(
+            resolve_script_import(&from, "./Missing", &tsconfig),
+            Resolution::NotFound
+        );
+        assert_eq!(
+            resolve_script_import(&from, "@/missing", &tsconfig),
+            Resolution::NotFound
+        );
+    }
+}
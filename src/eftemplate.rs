@@ -1,49 +1,254 @@
-use crate::error::Result;
+use crate::error::{Error, Result};
+use crate::language::Languages;
+use once_cell::sync::Lazy;
+use regex::{Captures, Regex};
+use std::collections::BTreeMap;
 use std::path::{Path, PathBuf};
 use std::{env, fs};
 
+const DEFAULT_TEMPLATE: &str = "{filePath}\n```{language}\n{content}\n```\n\
+#efsection command\n\
+`$ {command}` (exit code: {exitCode})\n```\n{output}\n```\n\
+#efsection tree\n\
+```\n{tree}\n```\n";
+
+/// フロントマターの`format`で指定できる組み込みの出力形式
+const PRESETS: &[(&str, &str)] = &[
+    ("markdown", DEFAULT_TEMPLATE),
+    (
+        "xml",
+        "<file path=\"{filePath}\" language=\"{language}\">\n{content}\n</file>\n\
+#efsection command\n\
+<command line=\"{command}\" exit_code=\"{exitCode}\">\n{output}\n</command>\n\
+#efsection tree\n\
+<tree root=\"{root}\">\n{tree}\n</tree>\n",
+    ),
+    (
+        "plain",
+        "{filePath}\n{content}\n#efsection command\n$ {command}\n{output}\n#efsection tree\n{tree}\n",
+    ),
+];
+
+/// `.eftemplate`内で、ファイル以外の出力の書式を始める行（`#efsection command`）
+const SECTION_PREFIX: &str = "#efsection ";
+
+static PLACEHOLDER: Lazy<Regex> = Lazy::new(|| Regex::new(r"\{(\w+)\}").unwrap());
+
 pub struct EfTemplate {
     template: String,
+    /// `#efsection`で定義した書式（定義されていないものは組み込みの書式を使う）
+    sections: BTreeMap<String, String>,
+    languages: Languages,
+    base_dir: Option<PathBuf>,
+    variables: BTreeMap<String, String>,
 }
 
-impl EfTemplate {
-    pub fn find_and_load<P: AsRef<Path>>(start_path: P) -> Result<Self> {
-        if let Some(template_path) = Self::find_template(&start_path)? {
-            let content = fs::read_to_string(template_path)?;
-            Ok(Self { template: content })
+/// `start_path`から親ディレクトリを順に辿り、最初に見つかった`file_name`のパスを返す
+pub(crate) fn find_upwards<P: AsRef<Path>>(
+    start_path: P,
+    file_name: &str,
+) -> Result<Option<PathBuf>> {
+    let start_path = start_path.as_ref();
+    let current_dir = if start_path.is_file() {
+        start_path.parent().unwrap_or_else(|| Path::new("."))
+    } else {
+        start_path
+    };
+
+    let mut current_dir = current_dir.canonicalize()?;
+
+    loop {
+        let candidate = current_dir.join(file_name);
+        if candidate.is_file() {
+            return Ok(Some(candidate));
+        }
+
+        if let Some(parent) = current_dir.parent() {
+            current_dir = parent.to_path_buf();
         } else {
-            Ok(Self::default())
+            break;
         }
     }
 
-    fn find_template<P: AsRef<Path>>(start_path: P) -> Result<Option<PathBuf>> {
-        let start_path = start_path.as_ref();
-        let current_dir = if start_path.is_file() {
-            start_path.parent().unwrap_or_else(|| Path::new("."))
+    Ok(None)
+}
+
+/// `.eftemplate`の内容を、ファイルの書式と`#efsection`ごとの書式に分ける
+fn split_sections(template: &str) -> (String, BTreeMap<String, String>) {
+    let mut file_template = String::new();
+    let mut sections = BTreeMap::new();
+    let mut current: Option<(String, String)> = None;
+
+    for line in template.split_inclusive('\n') {
+        if let Some(name) = line.trim_end().strip_prefix(SECTION_PREFIX) {
+            if let Some((name, section)) = current.take() {
+                sections.insert(name, section);
+            }
+            current = Some((name.trim().to_string(), String::new()));
+            continue;
+        }
+
+        match &mut current {
+            Some((_, section)) => section.push_str(line),
+            None => file_template.push_str(line),
+        }
+    }
+    if let Some((name, section)) = current {
+        sections.insert(name, section);
+    }
+
+    (file_template, sections)
+}
+
+impl EfTemplate {
+    pub fn find_and_load<P: AsRef<Path>>(start_path: P) -> Result<Self> {
+        let languages = Languages::find_and_load(&start_path)?;
+        let template = if let Some(template_path) = Self::find_template(&start_path)? {
+            fs::read_to_string(template_path)?
         } else {
-            start_path
+            DEFAULT_TEMPLATE.to_string()
         };
 
-        let mut current_dir = current_dir.canonicalize()?;
+        Ok(Self::new(template, languages))
+    }
 
-        loop {
-            let template_path = current_dir.join(".eftemplate");
-            if template_path.is_file() {
-                return Ok(Some(template_path));
-            }
+    /// 指定された.eftemplateファイルを読み込む
+    pub fn from_file<P: AsRef<Path>>(path: P) -> Result<Self> {
+        let path = path.as_ref();
+        let template = fs::read_to_string(path)?;
+        let languages = Languages::find_and_load(path)?;
 
-            if let Some(parent) = current_dir.parent() {
-                current_dir = parent.to_path_buf();
-            } else {
-                break;
-            }
+        Ok(Self::new(template, languages))
+    }
+
+    /// 組み込みの出力形式（`markdown`, `xml`, `plain`）を使う
+    pub fn from_preset<P: AsRef<Path>>(name: &str, start_path: P) -> Result<Self> {
+        let (_, template) = PRESETS
+            .iter()
+            .find(|(preset, _)| *preset == name)
+            .ok_or_else(|| Error::UnknownFormat {
+                name: name.to_string(),
+            })?;
+        let languages = Languages::find_and_load(start_path)?;
+
+        Ok(Self::new(template.to_string(), languages))
+    }
+
+    fn new(template: String, languages: Languages) -> Self {
+        let (template, mut sections) = split_sections(&template);
+        let (_, default_sections) = split_sections(DEFAULT_TEMPLATE);
+        for (name, section) in default_sections {
+            sections.entry(name).or_insert(section);
+        }
+
+        Self {
+            template,
+            sections,
+            languages,
+            base_dir: None,
+            variables: BTreeMap::new(),
         }
+    }
+
+    /// `{filePath}`を相対パスにする際の基準ディレクトリを指定する（デフォルトはカレントディレクトリ）
+    pub fn with_base_dir(mut self, base_dir: PathBuf) -> Self {
+        self.base_dir = Some(base_dir);
+        self
+    }
+
+    /// テンプレート内で`{name}`として参照できる変数を追加する
+    pub fn with_variables(mut self, variables: BTreeMap<String, String>) -> Self {
+        self.variables.extend(variables);
+        self
+    }
 
-        Ok(None)
+    fn find_template<P: AsRef<Path>>(start_path: P) -> Result<Option<PathBuf>> {
+        find_upwards(start_path, ".eftemplate")
     }
 
     pub fn format(&self, file_path: &Path, content: &str) -> String {
-        let current_dir = env::current_dir().unwrap_or_else(|_| PathBuf::from("."));
+        let placeholders = BTreeMap::from([
+            ("startLine", "1".to_string()),
+            ("endLine", content.lines().count().to_string()),
+        ]);
+        self.format_with(file_path, content, &placeholders)
+    }
+
+    /// 追加のプレースホルダーを指定して整形する
+    ///
+    /// `placeholders`に`filePath`や`language`を含めると、組み込みの値の代わりに使われる。
+    pub fn format_with(
+        &self,
+        file_path: &Path,
+        content: &str,
+        placeholders: &BTreeMap<&str, String>,
+    ) -> String {
+        let normalized_path = self.display_path(file_path);
+        let language = match placeholders.get("language") {
+            Some(language) => language.clone(),
+            None => self.detect_language(file_path, content),
+        };
+
+        // ファイル内容に含まれる`{...}`を置換しないよう、テンプレートを一度だけ走査する
+        PLACEHOLDER
+            .replace_all(&self.template, |captures: &Captures| {
+                let name = &captures[1];
+                if name == "content" {
+                    return content.to_string();
+                }
+                if let Some(value) = placeholders.get(name) {
+                    return value.clone();
+                }
+
+                match name {
+                    "filePath" => normalized_path.as_str(),
+                    "language" => language.as_str(),
+                    name => self
+                        .variables
+                        .get(name)
+                        .map(String::as_str)
+                        .unwrap_or(&captures[0]),
+                }
+                .to_string()
+            })
+            .into_owned()
+    }
+
+    /// `#efsection`で定義した書式で整形する
+    ///
+    /// 未知のプレースホルダーはフロントマターの変数で置き換え、それもなければそのまま残す。
+    pub fn format_section(&self, name: &str, placeholders: &BTreeMap<&str, String>) -> String {
+        let Some(section) = self.sections.get(name) else {
+            return String::new();
+        };
+
+        PLACEHOLDER
+            .replace_all(section, |captures: &Captures| {
+                let name = &captures[1];
+                placeholders
+                    .get(name)
+                    .or_else(|| self.variables.get(name))
+                    .cloned()
+                    .unwrap_or_else(|| captures[0].to_string())
+            })
+            .into_owned()
+    }
+
+    /// ファイルの言語識別子を返す（判定できない場合は空文字）
+    pub fn detect_language(&self, file_path: &Path, content: &str) -> String {
+        self.languages
+            .detect(file_path, content)
+            .unwrap_or_default()
+    }
+
+    /// `{filePath}`として表示するパスを返す
+    ///
+    /// 基準ディレクトリ（デフォルトはカレントディレクトリ）以下のパスは相対パスに、それ以外は絶対パスにする。
+    pub fn display_path(&self, file_path: &Path) -> String {
+        let current_dir = match &self.base_dir {
+            Some(base_dir) => base_dir.clone(),
+            None => env::current_dir().unwrap_or_else(|_| PathBuf::from(".")),
+        };
 
         let relative_path = if file_path.is_absolute() {
             if let (Ok(canonical_file), Ok(canonical_current)) =
@@ -62,23 +267,17 @@ impl EfTemplate {
             file_path.to_path_buf()
         };
 
-        let normalized_path = relative_path
+        relative_path
             .components()
             .collect::<PathBuf>()
             .to_string_lossy()
-            .into_owned();
-
-        self.template
-            .replace("{filePath}", &normalized_path)
-            .replace("{content}", content)
+            .into_owned()
     }
 }
 
 impl Default for EfTemplate {
     fn default() -> Self {
-        Self {
-            template: "{filePath}\n```\n{content}\n```\n".to_string(),
-        }
+        Self::new(DEFAULT_TEMPLATE.to_string(), Languages::default())
     }
 }
 
@@ -97,9 +296,109 @@ mod tests {
         let result = template.format(&file_path, content);
 
         assert!(result.contains("test.rs"));
+        assert!(result.contains("```rust\n"));
         assert!(result.contains(content));
     }
 
+    #[test]
+    fn test_unknown_language() {
+        let template = EfTemplate::default();
+        let result = template.format(Path::new("data.unknown"), "text");
+
+        assert_eq!(result, "data.unknown\n```\ntext\n```\n");
+    }
+
+    #[test]
+    fn test_presets() {
+        let temp_dir = tempdir().unwrap();
+        let template = EfTemplate::from_preset("xml", temp_dir.path()).unwrap();
+        let result = template.format(Path::new("src/main.rs"), "fn main() {}");
+
+        assert_eq!(
+            result,
+            "<file path=\"src/main.rs\" language=\"rust\">\nfn main() {}\n</file>\n"
+        );
+        assert!(matches!(
+            EfTemplate::from_preset("html", temp_dir.path()),
+            Err(Error::UnknownFormat { .. })
+        ));
+    }
+
+    #[test]
+    fn test_sections() {
+        let template = EfTemplate::new(
+            "FILE: {filePath}\n{content}\n#efsection command\nCMD[{project}]: {command} -> {exitCode}\n{output}\n"
+                .to_string(),
+            Languages::default(),
+        )
+        .with_variables(BTreeMap::from([("project".to_string(), "ef".to_string())]));
+
+        assert_eq!(
+            template.format(Path::new("a.txt"), "text"),
+            "FILE: a.txt\ntext\n"
+        );
+        let placeholders = BTreeMap::from([
+            ("command", "make".to_string()),
+            ("exitCode", "0".to_string()),
+            ("output", "{done}".to_string()),
+        ]);
+        assert_eq!(
+            template.format_section("command", &placeholders),
+            "CMD[ef]: make -> 0\n{done}\n"
+        );
+
+        // 定義されていない書式は組み込みのものを使う
+        let template = EfTemplate::new("{content}".to_string(), Languages::default());
+        assert_eq!(
+            template.format_section("command", &placeholders),
+            "`$ make` (exit code: 0)\n```\n{done}\n```\n"
+        );
+    }
+
+    #[test]
+    fn test_variables_and_placeholders_in_content() {
+        let template = EfTemplate::new(
+            "[{project}] {filePath} {unknown}\n{content}".to_string(),
+            Languages::default(),
+        )
+        .with_variables(BTreeMap::from([("project".to_string(), "ef".to_string())]));
+        let result = template.format(Path::new("a.txt"), "{filePath} {project}");
+
+        assert_eq!(result, "[ef] a.txt {unknown}\n{filePath} {project}");
+    }
+
+    #[test]
+    fn test_line_placeholders() {
+        let template = EfTemplate::new(
+            "{filePath}:{startLine}-{endLine} ({language})\n{content}".to_string(),
+            Languages::default(),
+        );
+
+        let result = template.format(Path::new("a.rs"), "a\nb\nc\n");
+        assert_eq!(result, "a.rs:1-3 (rust)\na\nb\nc\n");
+
+        let placeholders = BTreeMap::from([
+            ("startLine", "10".to_string()),
+            ("endLine", "11".to_string()),
+            ("language", "diff".to_string()),
+        ]);
+        let result = template.format_with(Path::new("a.rs"), "b\nc", &placeholders);
+        assert_eq!(result, "a.rs:10-11 (diff)\nb\nc");
+    }
+
+    #[test]
+    fn test_base_dir() {
+        let temp_dir = tempdir().unwrap();
+        let base_dir = temp_dir.path().canonicalize().unwrap();
+        fs::create_dir_all(base_dir.join("src")).unwrap();
+        fs::write(base_dir.join("src/main.rs"), "").unwrap();
+
+        let template = EfTemplate::default().with_base_dir(base_dir.clone());
+        let result = template.format(&base_dir.join("src/main.rs"), "");
+
+        assert!(result.starts_with("src/main.rs\n"), "got: {}", result);
+    }
+
     #[test]
     fn test_custom_template() {
         let temp_dir = tempdir().unwrap();
@@ -113,7 +412,7 @@ mod tests {
         let file_path = Path::new("src/main.rs");
         let content = "fn main() {\n    println!(\"Hello\");\n}";
 
-        let result = template.format(&file_path, content);
+        let result = template.format(file_path, content);
 
         assert!(result.contains("File: src/main.rs"));
         assert!(result.contains(content));
@@ -206,6 +505,7 @@ mod tests {
         assert!(result.contains("SUB: "));
         assert!(!result.contains("ROOT: "));
         assert!(result.contains("subdir/test.rs"));
+        assert!(result.contains("(rust)"));
 
         env::set_current_dir(original_dir).unwrap();
     }
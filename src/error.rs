@@ -1,6 +1,9 @@
+use std::fmt;
+use std::path::PathBuf;
 use thiserror::Error;
 
 #[derive(Error, Debug)]
+#[allow(clippy::enum_variant_names)]
 pub enum Error {
     #[error("Invalid glob pattern: {pattern}")]
     InvalidGlobPattern {
@@ -16,8 +19,93 @@ pub enum Error {
         source: regex::Error,
     },
 
+    #[error("Invalid language mappings: {}", path.display())]
+    InvalidLanguageMappings {
+        path: std::path::PathBuf,
+        #[source]
+        source: Box<ron::error::SpannedError>,
+    },
+
+    #[error("Invalid directive argument `{argument}`: {message}")]
+    InvalidDirectiveArgument { argument: String, message: String },
+
+    #[error("Invalid front matter: {message}")]
+    InvalidFrontMatter { message: String },
+
+    #[error("Unknown output format: {name} (available: markdown, xml, plain)")]
+    UnknownFormat { name: String },
+
+    #[error("Template not found: {}", path.display())]
+    TemplateNotFound {
+        path: std::path::PathBuf,
+        #[source]
+        source: std::io::Error,
+    },
+
+    #[error("Include cycle detected: {}", format_chain(chain))]
+    IncludeCycle { chain: Vec<std::path::PathBuf> },
+
+    #[error("Undefined template variables: {}", format_undefined(variables))]
+    UndefinedVariables { variables: Vec<UndefinedVariable> },
+
+    #[error("Template syntax error at {location}: {message}")]
+    TemplateSyntax { location: Location, message: String },
+
+    #[error("#efcmd is disabled: pass --allow-commands to run `{command}`")]
+    CommandsNotAllowed { command: String },
+
+    #[error("Failed to run `{command}`")]
+    CommandFailed {
+        command: String,
+        #[source]
+        source: std::io::Error,
+    },
+
+    #[error("git {command} failed: {message}")]
+    Git { command: String, message: String },
+
     #[error(transparent)]
     IoError(#[from] std::io::Error),
 }
 
+fn format_chain(chain: &[std::path::PathBuf]) -> String {
+    chain
+        .iter()
+        .map(|path| path.display().to_string())
+        .collect::<Vec<_>>()
+        .join(" -> ")
+}
+
+/// テンプレート内の位置（`#efinclude`で取り込んだファイルも含む）
+#[derive(Debug, Clone, PartialEq)]
+pub struct Location {
+    /// テンプレートファイル（文字列から読み込んだ場合は`None`）
+    pub file: Option<PathBuf>,
+    /// 1始まりの行番号
+    pub line: usize,
+}
+
+impl fmt::Display for Location {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.file {
+            Some(file) => write!(f, "{}:{}", file.display(), self.line),
+            None => write!(f, "line {}", self.line),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct UndefinedVariable {
+    pub name: String,
+    pub location: Location,
+}
+
+fn format_undefined(variables: &[UndefinedVariable]) -> String {
+    variables
+        .iter()
+        .map(|variable| format!("{} ({})", variable.name, variable.location))
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
 pub type Result<T> = std::result::Result<T, Error>;
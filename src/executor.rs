@@ -1,12 +1,26 @@
 use crate::cli;
+use crate::command;
+use crate::comment;
 use crate::eftemplate;
 use crate::error;
 use crate::error::Error;
+use crate::extract::{self, LineRange};
+use crate::front_matter::FrontMatter;
+use crate::git;
 use crate::path_resolver;
+use crate::rust_module;
+use crate::symbol;
 use crate::template;
+use crate::tree;
 use crate::warning;
+use std::collections::BTreeMap;
+use std::fs;
 use std::io::Write;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+/// トークン数の概算に使う、1トークンあたりの文字数
+const CHARS_PER_TOKEN: usize = 4;
 
 pub fn execute<W: Write>(
     cli: cli::Cli,
@@ -15,39 +29,452 @@ pub fn execute<W: Write>(
 ) -> error::Result<()> {
     let template_path = PathBuf::from(&cli.template_path)
         .canonicalize()
-        .map_err(|e| Error::IoError(e))?;
+        .map_err(Error::IoError)?;
+
+    let defines = cli.defines.into_iter().collect();
+    let template = template::Template::from_file(&template_path, &defines)?;
+    let front_matter = template.front_matter();
+
+    // 何かを出力する前に、許可されていないコマンドがないか確かめる
+    if !cli.allow_commands {
+        let command = template.lines().iter().find_map(|line| match line {
+            template::TemplateLine::Directive(template::Directive::Command(command)) => {
+                Some(command)
+            }
+            _ => None,
+        });
+        if let Some(command) = command {
+            return Err(Error::CommandsNotAllowed {
+                command: command.clone(),
+            });
+        }
+    }
 
-    let template = template::Template::from_file(&template_path)?;
     let mut resolver = path_resolver::PathResolver::new()?;
-    let eftemplate = eftemplate::EfTemplate::find_and_load(&template_path)?;
+    resolver.add_ignore_patterns(&front_matter.ignore)?;
+    resolver.set_selection(cli.select);
+    if !cli.no_ignore {
+        resolver.use_ignore_files(&template_path)?;
+    }
+
+    let mut executor = Executor {
+        resolver,
+        eftemplate: load_eftemplate(&template_path, front_matter)?,
+        template_dir: template_path
+            .parent()
+            .unwrap_or_else(|| Path::new("."))
+            .to_path_buf(),
+        command_timeout: Duration::from_secs(cli.command_timeout),
+        writer,
+        written_chars: 0,
+        warnings,
+    };
 
     for line in template.lines() {
         match line {
-            template::TemplateLine::Text(text) => {
-                writeln!(writer, "{}", text)?;
-            }
-            template::TemplateLine::Directive(directive) => {
-                let paths = match &directive {
-                    template::Directive::Glob(pattern) => resolver.resolve_glob(pattern)?,
-                    template::Directive::Regex(pattern) => resolver.resolve_regex(pattern)?,
-                };
-
-                for path in paths {
-                    match std::fs::read_to_string(&path) {
-                        Ok(content) => {
-                            let formatted = eftemplate.format(&path, &content);
-                            writeln!(writer, "{}", formatted)?;
-                        }
-                        Err(e) => {
-                            warnings.push(warning::Warning::FileNotFound { path: path.clone() });
-                            eprintln!("Failed to read file {}: {}", path.display(), e);
-                        }
-                    }
+            template::TemplateLine::Text(text) => executor.write(text)?,
+            template::TemplateLine::Directive(directive) => executor.run_directive(directive)?,
+        }
+    }
+
+    if let Some(budget) = front_matter.token_budget {
+        let estimated = executor.written_chars.div_ceil(CHARS_PER_TOKEN);
+        if estimated > budget {
+            executor
+                .warnings
+                .push(warning::Warning::TokenBudgetExceeded { estimated, budget });
+        }
+    }
+
+    let resolver_warnings = executor.resolver.take_warnings();
+    executor.warnings.extend(resolver_warnings);
+    Ok(())
+}
+
+/// テンプレートの展開中の状態
+struct Executor<'a, W: Write> {
+    resolver: path_resolver::PathResolver,
+    eftemplate: eftemplate::EfTemplate,
+    /// `#efcmd`を実行するディレクトリ
+    template_dir: PathBuf,
+    command_timeout: Duration,
+    writer: &'a mut W,
+    /// 出力した文字数（トークン数の概算に使う）
+    written_chars: usize,
+    warnings: &'a mut warning::Warnings,
+}
+
+impl<W: Write> Executor<'_, W> {
+    /// 1行（または整形済みのブロック）を改行付きで出力する
+    fn write(&mut self, text: &str) -> error::Result<()> {
+        writeln!(self.writer, "{}", text)?;
+        self.written_chars += text.chars().count() + 1;
+        Ok(())
+    }
+
+    fn run_directive(&mut self, directive: &template::Directive) -> error::Result<()> {
+        match directive {
+            template::Directive::Glob(pattern) => {
+                let paths = self.resolver.resolve_glob(pattern)?;
+                self.embed_files(paths)
+            }
+            template::Directive::Regex(pattern) => {
+                let paths = self.resolver.resolve_regex(pattern)?;
+                self.embed_files(paths)
+            }
+            template::Directive::Exclude(pattern) => self.resolver.add_exclude_argument(pattern),
+            template::Directive::Lines { pattern, range } => self.embed_lines(pattern, range),
+            template::Directive::Region { pattern, name } => self.embed_region(pattern, name),
+            template::Directive::Symbol { pattern, symbol } => self.embed_symbol(pattern, symbol),
+            template::Directive::ModuleTree { root, depth } => {
+                let paths = self.resolver.resolve_module_tree(root, *depth)?;
+                self.embed_files(paths)
+            }
+            template::Directive::Dependencies { entry, depth } => {
+                let paths = self.resolver.resolve_dependencies(entry, *depth)?;
+                self.embed_files(paths)
+            }
+            template::Directive::RustItem {
+                item,
+                root,
+                include_tests,
+            } => self.embed_rust_item(item, root.as_deref(), *include_tests),
+            template::Directive::Command(command) => self.embed_command(command),
+            template::Directive::Tree {
+                root,
+                depth,
+                include_hidden,
+                sizes,
+                line_counts,
+            } => self.embed_tree(root, *depth, *include_hidden, *sizes, *line_counts),
+            template::Directive::Revision(paths) => self.embed_revision(paths),
+            template::Directive::Diff(options) => self.embed_diff(options),
+            template::Directive::Changed {
+                base,
+                include_untracked,
+            } => {
+                let paths = self
+                    .resolver
+                    .resolve_changed(base.as_deref(), *include_untracked)?;
+                self.embed_files(paths)
+            }
+        }
+    }
+
+    /// リビジョンでのファイルの内容を埋め込む（`{filePath}`は`path@revision`）
+    fn embed_revision(&mut self, paths: &[git::RevisionPath]) -> error::Result<()> {
+        let current_dir = std::env::current_dir()?;
+        for revision_path in paths {
+            for path in self.resolver.resolve_revision(revision_path)? {
+                let content = git::show(&current_dir, &revision_path.revision, &path)?;
+                // バイナリファイルは作業ツリーと同様に除外する
+                if content.iter().take(1024).any(|&byte| byte == 0) {
+                    continue;
                 }
+                let content = String::from_utf8_lossy(&content);
+
+                // 作業ツリーに残っていれば、そのパスを基準に表示する
+                let full_path = current_dir.join(&path);
+                let path = if full_path.exists() { full_path } else { path };
+                let placeholders = BTreeMap::from([
+                    (
+                        "filePath",
+                        format!(
+                            "{}@{}",
+                            self.eftemplate.display_path(&path),
+                            revision_path.revision
+                        ),
+                    ),
+                    ("revision", revision_path.revision.clone()),
+                    ("startLine", "1".to_string()),
+                    ("endLine", content.lines().count().to_string()),
+                ]);
+                let formatted = self.eftemplate.format_with(&path, &content, &placeholders);
+                self.write(&formatted)?;
             }
         }
+        Ok(())
     }
 
-    warnings.extend(resolver.take_warnings());
-    Ok(())
+    /// 差分をファイルごとに埋め込む（`{language}`は`diff`）
+    fn embed_diff(&mut self, options: &git::DiffOptions) -> error::Result<()> {
+        let current_dir = std::env::current_dir()?.canonicalize()?;
+        let root = git::repository_root(&current_dir)?;
+        let patches = git::diff(&current_dir, options)?;
+        if patches.is_empty() {
+            self.warnings.push(warning::Warning::EmptyDiff {
+                revisions: options.revisions.as_deref().unwrap_or("HEAD").to_string(),
+            });
+        }
+
+        let placeholders = BTreeMap::from([("language", "diff".to_string())]);
+        for patch in patches {
+            let path = root.join(&patch.path);
+            // 削除されたファイルは正規化できないので、カレントディレクトリからの相対パスにしておく
+            let path = match path.exists() {
+                true => path,
+                false => pathdiff::diff_paths(&path, &current_dir).unwrap_or(path),
+            };
+            let formatted = self
+                .eftemplate
+                .format_with(&path, &patch.patch, &placeholders);
+            self.write(&formatted)?;
+        }
+        Ok(())
+    }
+
+    fn embed_tree(
+        &mut self,
+        root: &str,
+        depth: Option<usize>,
+        include_hidden: bool,
+        sizes: bool,
+        line_counts: bool,
+    ) -> error::Result<()> {
+        let Some((root_path, entries)) = self.resolver.resolve_tree(root, depth, include_hidden)?
+        else {
+            return Ok(());
+        };
+
+        let annotate = |path: &Path| {
+            let mut annotations = Vec::new();
+            if sizes {
+                let size = fs::metadata(path).map(|metadata| metadata.len()).ok()?;
+                annotations.push(tree::format_size(size));
+            }
+            if line_counts {
+                let content = fs::read_to_string(path).ok()?;
+                annotations.push(format!("{} lines", content.lines().count()));
+            }
+            (!annotations.is_empty()).then(|| annotations.join(", "))
+        };
+
+        let root_label = match self.eftemplate.display_path(&root_path) {
+            // カレントディレクトリ自体は空のパスになる
+            path if path.is_empty() => "./".to_string(),
+            path => format!("{}/", path),
+        };
+        let placeholders = BTreeMap::from([
+            ("root", root_label.clone()),
+            ("tree", tree::render(&root_label, &entries, &annotate)),
+        ]);
+        let formatted = self.eftemplate.format_section("tree", &placeholders);
+        self.write(&formatted)
+    }
+
+    fn embed_command(&mut self, command: &str) -> error::Result<()> {
+        let output =
+            command::run(command, &self.template_dir, self.command_timeout).map_err(|source| {
+                Error::CommandFailed {
+                    command: command.to_string(),
+                    source,
+                }
+            })?;
+
+        let status = match output.status {
+            Some(status) if status.success() => None,
+            Some(status) => Some(match status.code() {
+                Some(code) => format!("exited with code {}", code),
+                None => "was terminated by a signal".to_string(),
+            }),
+            None => Some(format!(
+                "timed out after {}s",
+                self.command_timeout.as_secs()
+            )),
+        };
+        if let Some(status) = status {
+            self.warnings.push(warning::Warning::CommandFailed {
+                command: command.to_string(),
+                status,
+            });
+        }
+
+        let placeholders = BTreeMap::from([
+            ("command", command.to_string()),
+            ("exitCode", output.exit_code()),
+            ("output", output.combined()),
+            ("stdout", output.stdout),
+            ("stderr", output.stderr),
+        ]);
+        let formatted = self.eftemplate.format_section("command", &placeholders);
+        self.write(&formatted)
+    }
+
+    fn embed_files(&mut self, paths: Vec<PathBuf>) -> error::Result<()> {
+        for path in paths {
+            if let Some(content) = self.read_file(&path) {
+                let formatted = self.eftemplate.format(&path, &content);
+                self.write(&formatted)?;
+            }
+        }
+        Ok(())
+    }
+
+    fn embed_lines(&mut self, pattern: &str, range: &LineRange) -> error::Result<()> {
+        for path in self.resolver.resolve_glob_terms(&[pattern.to_string()])? {
+            let Some(content) = self.read_file(&path) else {
+                continue;
+            };
+
+            let (extracted, out_of_bounds) = range.extract(&content);
+            if out_of_bounds {
+                self.warnings.push(warning::Warning::LineRangeOutOfBounds {
+                    path: path.clone(),
+                    range: range.to_string(),
+                    line_count: content.lines().count(),
+                });
+            }
+
+            if let Some(extracted) = extracted {
+                self.write_extracted(&path, &content, &extracted, BTreeMap::new())?;
+            }
+        }
+        Ok(())
+    }
+
+    fn embed_region(&mut self, pattern: &str, name: &str) -> error::Result<()> {
+        for path in self.resolver.resolve_glob_terms(&[pattern.to_string()])? {
+            let Some(content) = self.read_file(&path) else {
+                continue;
+            };
+
+            let language = self.eftemplate.detect_language(&path, &content);
+            let prefixes = comment::prefixes(Some(language.as_str()).filter(|l| !l.is_empty()));
+
+            match extract::extract_region(&content, name, prefixes) {
+                Ok(extracted) => {
+                    let placeholders = BTreeMap::from([("region", name.to_string())]);
+                    self.write_extracted(&path, &content, &extracted, placeholders)?;
+                }
+                Err(extract::RegionError::NotFound) => {
+                    self.warnings.push(warning::Warning::RegionNotFound {
+                        path,
+                        name: name.to_string(),
+                    })
+                }
+                Err(extract::RegionError::Unbalanced(message)) => self
+                    .warnings
+                    .push(warning::Warning::UnbalancedRegion { path, message }),
+            }
+        }
+        Ok(())
+    }
+
+    fn embed_symbol(&mut self, pattern: &str, symbol: &str) -> error::Result<()> {
+        for path in self.resolver.resolve_glob_terms(&[pattern.to_string()])? {
+            let Some(content) = self.read_file(&path) else {
+                continue;
+            };
+
+            let language = self.eftemplate.detect_language(&path, &content);
+            match symbol::extract(&content, &language, symbol) {
+                Ok(extracted) => {
+                    let placeholders = BTreeMap::from([("symbol", symbol.to_string())]);
+                    self.write_extracted(&path, &content, &extracted, placeholders)?;
+                }
+                Err(symbol::SymbolError::NotFound) => {
+                    self.warnings.push(warning::Warning::SymbolNotFound {
+                        path,
+                        symbol: symbol.to_string(),
+                    })
+                }
+                Err(symbol::SymbolError::UnsupportedLanguage) => self
+                    .warnings
+                    .push(warning::Warning::UnsupportedSymbolLanguage { path, language }),
+            }
+        }
+        Ok(())
+    }
+
+    fn embed_rust_item(
+        &mut self,
+        item: &str,
+        root: Option<&Path>,
+        include_tests: bool,
+    ) -> error::Result<()> {
+        let crate_root = match root {
+            Some(root) => Some(root.to_path_buf()),
+            None => rust_module::find_crate_root(std::env::current_dir()?),
+        };
+        let Some(crate_root) = crate_root else {
+            self.warnings.push(warning::Warning::RustItemNotFound {
+                item: item.to_string(),
+                message: "Cargo.toml with src/lib.rs or src/main.rs not found".to_string(),
+            });
+            return Ok(());
+        };
+
+        let item_path = item.split("::").skip(1).collect::<Vec<_>>();
+        match rust_module::find_item(&crate_root, &item_path, include_tests) {
+            Ok((file, extracted)) => {
+                let placeholders = BTreeMap::from([("symbol", item.to_string())]);
+                self.write_extracted(&file.path, &file.content, &extracted, placeholders)
+            }
+            Err(message) => {
+                self.warnings.push(warning::Warning::RustItemNotFound {
+                    item: item.to_string(),
+                    message,
+                });
+                Ok(())
+            }
+        }
+    }
+
+    /// ファイルから切り出した部分を整形して出力する
+    ///
+    /// 言語の判定には切り出す前の内容を使う。
+    fn write_extracted(
+        &mut self,
+        path: &Path,
+        content: &str,
+        extracted: &extract::Extracted,
+        mut placeholders: BTreeMap<&str, String>,
+    ) -> error::Result<()> {
+        placeholders.insert("startLine", extracted.start_line.to_string());
+        placeholders.insert("endLine", extracted.end_line.to_string());
+        placeholders.insert("language", self.eftemplate.detect_language(path, content));
+        let formatted = self
+            .eftemplate
+            .format_with(path, &extracted.content, &placeholders);
+        self.write(&formatted)
+    }
+
+    /// ファイルを読み込む。読み込めない場合は警告を記録して`None`を返す
+    fn read_file(&mut self, path: &Path) -> Option<String> {
+        match std::fs::read_to_string(path) {
+            Ok(content) => Some(content),
+            Err(e) => {
+                self.warnings.push(warning::Warning::FileNotFound {
+                    path: path.to_path_buf(),
+                });
+                eprintln!("Failed to read file {}: {}", path.display(), e);
+                None
+            }
+        }
+    }
+}
+
+/// フロントマターの設定に従って.eftemplateを読み込む
+///
+/// `eftemplate` > `format` > .eftemplateの探索 の順に優先する。
+fn load_eftemplate(
+    template_path: &Path,
+    front_matter: &FrontMatter,
+) -> error::Result<eftemplate::EfTemplate> {
+    let template_dir = template_path.parent().unwrap_or_else(|| Path::new("."));
+
+    let eftemplate = if let Some(path) = &front_matter.eftemplate {
+        eftemplate::EfTemplate::from_file(template_dir.join(path))?
+    } else if let Some(format) = &front_matter.format {
+        eftemplate::EfTemplate::from_preset(format, template_path)?
+    } else {
+        eftemplate::EfTemplate::find_and_load(template_path)?
+    };
+
+    let eftemplate = match &front_matter.path_base {
+        Some(path_base) => eftemplate.with_base_dir(template_dir.join(path_base).canonicalize()?),
+        None => eftemplate,
+    };
+
+    Ok(eftemplate.with_variables(front_matter.variables.clone()))
 }
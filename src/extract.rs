@@ -0,0 +1,329 @@
+use crate::comment;
+use std::fmt;
+
+/// ファイル内容から取り出した一部分
+#[derive(Debug, PartialEq)]
+pub struct Extracted {
+    pub content: String,
+    /// 取り出した範囲の先頭行（1始まり）
+    pub start_line: usize,
+    /// 取り出した範囲の最終行（1始まり、この行を含む）
+    pub end_line: usize,
+}
+
+/// `path:10-40`形式で指定する行範囲（1始まり、両端を含む）
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct LineRange {
+    pub start: usize,
+    /// `None`の場合はファイルの末尾まで
+    pub end: Option<usize>,
+}
+
+impl LineRange {
+    /// `path:10-40`, `path:100-`, `path:-20`, `path:15` のような項から、パスと行範囲を分離する
+    ///
+    /// 行範囲の指定がない場合は`None`を返す。範囲の指定が不正な場合は`Some(Err(..))`を返す。
+    pub fn split_suffix(term: &str) -> Option<(&str, Result<Self, String>)> {
+        let (path, range) = term.rsplit_once(':')?;
+        if path.is_empty()
+            || range.is_empty()
+            || !range.chars().all(|c| c.is_ascii_digit() || c == '-')
+        {
+            return None;
+        }
+
+        let parse = |value: &str| -> Result<Option<usize>, String> {
+            if value.is_empty() {
+                Ok(None)
+            } else {
+                value
+                    .parse()
+                    .map(Some)
+                    .map_err(|_| format!("invalid line number `{}`", value))
+            }
+        };
+
+        let range = match range.split_once('-') {
+            Some((start, end)) if start.is_empty() && end.is_empty() => return None,
+            Some((start, end)) => parse(start).and_then(|start| {
+                Ok(Self {
+                    start: start.unwrap_or(1),
+                    end: parse(end)?,
+                })
+            }),
+            None => parse(range).map(|line| Self {
+                start: line.unwrap_or(1),
+                end: line,
+            }),
+        }
+        .and_then(|range| range.validate());
+
+        Some((path, range))
+    }
+
+    fn validate(self) -> Result<Self, String> {
+        if self.start == 0 {
+            return Err("line numbers start at 1".to_string());
+        }
+        if matches!(self.end, Some(end) if end < self.start) {
+            return Err(format!("end line is before start line in `{}`", self));
+        }
+        Ok(self)
+    }
+
+    /// 範囲内の行を取り出す
+    ///
+    /// 範囲がファイルの行数を超える部分は切り詰め、2つめの値を`true`にする。
+    /// 開始行がファイルの行数を超える場合は、取り出す行が無いため`None`を返す。
+    pub fn extract(&self, content: &str) -> (Option<Extracted>, bool) {
+        let lines = content.lines().collect::<Vec<_>>();
+        let line_count = lines.len();
+        if self.start > line_count {
+            return (None, true);
+        }
+
+        let end = self.end.unwrap_or(line_count);
+        let end_line = end.min(line_count);
+        let extracted = Extracted {
+            content: lines[self.start - 1..end_line].join("\n"),
+            start_line: self.start,
+            end_line,
+        };
+
+        (Some(extracted), end > line_count)
+    }
+}
+
+impl fmt::Display for LineRange {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.end {
+            Some(end) => write!(f, "{}-{}", self.start, end),
+            None => write!(f, "{}-", self.start),
+        }
+    }
+}
+
+/// 領域の抽出に失敗した理由
+#[derive(Debug, PartialEq)]
+pub enum RegionError {
+    /// `ef:begin`と`ef:end`のどちらも見つからない
+    NotFound,
+    /// マーカーの対応が取れていない
+    Unbalanced(String),
+}
+
+enum Marker<'a> {
+    Begin(&'a str),
+    End(&'a str),
+}
+
+/// `ef:begin name`と`ef:end name`のコメントで囲まれた領域を取り出す
+///
+/// 領域内にある別の領域のマーカー行は取り除く。同じ名前の領域が複数ある場合は最初のものを使う。
+pub fn extract_region(
+    content: &str,
+    name: &str,
+    comment_prefixes: &[&str],
+) -> Result<Extracted, RegionError> {
+    let lines = content.lines().collect::<Vec<_>>();
+    let mut begin = None;
+
+    for (i, line) in lines.iter().enumerate() {
+        let line_number = i + 1;
+        match (parse_marker(line, comment_prefixes), begin) {
+            (Some(Marker::Begin(marker)), None) if marker == name => begin = Some(i),
+            (Some(Marker::Begin(marker)), Some(begin)) if marker == name => {
+                return Err(RegionError::Unbalanced(format!(
+                    "`ef:begin {}` at line {} is opened again at line {}",
+                    name,
+                    begin + 1,
+                    line_number
+                )));
+            }
+            (Some(Marker::End(marker)), None) if marker == name => {
+                return Err(RegionError::Unbalanced(format!(
+                    "`ef:end {}` at line {} has no matching `ef:begin`",
+                    name, line_number
+                )));
+            }
+            (Some(Marker::End(marker)), Some(begin)) if marker == name => {
+                let content = lines[begin + 1..i]
+                    .iter()
+                    .filter(|line| parse_marker(line, comment_prefixes).is_none())
+                    .copied()
+                    .collect::<Vec<_>>()
+                    .join("\n");
+
+                return Ok(Extracted {
+                    content,
+                    start_line: begin + 2,
+                    end_line: i,
+                });
+            }
+            _ => {}
+        }
+    }
+
+    match begin {
+        Some(begin) => Err(RegionError::Unbalanced(format!(
+            "`ef:begin {}` at line {} has no matching `ef:end`",
+            name,
+            begin + 1
+        ))),
+        None => Err(RegionError::NotFound),
+    }
+}
+
+fn parse_marker<'a>(line: &'a str, comment_prefixes: &[&str]) -> Option<Marker<'a>> {
+    let body = comment::strip(line, comment_prefixes)?;
+    if let Some(name) = body.strip_prefix("ef:begin ") {
+        Some(Marker::Begin(name.trim()))
+    } else {
+        body.strip_prefix("ef:end ")
+            .map(|name| Marker::End(name.trim()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn range(start: usize, end: Option<usize>) -> LineRange {
+        LineRange { start, end }
+    }
+
+    #[test]
+    fn test_split_suffix() {
+        assert_eq!(
+            LineRange::split_suffix("src/executor.rs:10-40"),
+            Some(("src/executor.rs", Ok(range(10, Some(40)))))
+        );
+        assert_eq!(
+            LineRange::split_suffix("src/executor.rs:100-"),
+            Some(("src/executor.rs", Ok(range(100, None))))
+        );
+        assert_eq!(
+            LineRange::split_suffix("a.rs:-20"),
+            Some(("a.rs", Ok(range(1, Some(20)))))
+        );
+        assert_eq!(
+            LineRange::split_suffix("a.rs:15"),
+            Some(("a.rs", Ok(range(15, Some(15)))))
+        );
+    }
+
+    #[test]
+    fn test_split_suffix_without_range() {
+        assert_eq!(LineRange::split_suffix("src/*.rs"), None);
+        assert_eq!(LineRange::split_suffix("C:\\src\\main.rs"), None);
+        assert_eq!(LineRange::split_suffix("a.rs:-"), None);
+        assert_eq!(LineRange::split_suffix("a.rs:"), None);
+    }
+
+    #[test]
+    fn test_split_suffix_invalid_range() {
+        assert!(matches!(
+            LineRange::split_suffix("a.rs:40-10"),
+            Some((_, Err(_)))
+        ));
+        assert!(matches!(
+            LineRange::split_suffix("a.rs:0-10"),
+            Some((_, Err(_)))
+        ));
+        assert!(matches!(
+            LineRange::split_suffix("a.rs:1-2-3"),
+            Some((_, Err(_)))
+        ));
+    }
+
+    #[test]
+    fn test_extract() {
+        let content = "one\ntwo\nthree\nfour\n";
+
+        let (extracted, out_of_bounds) = range(2, Some(3)).extract(content);
+        let extracted = extracted.unwrap();
+        assert_eq!(extracted.content, "two\nthree");
+        assert_eq!((extracted.start_line, extracted.end_line), (2, 3));
+        assert!(!out_of_bounds);
+
+        let (extracted, out_of_bounds) = range(3, None).extract(content);
+        let extracted = extracted.unwrap();
+        assert_eq!(extracted.content, "three\nfour");
+        assert_eq!((extracted.start_line, extracted.end_line), (3, 4));
+        assert!(!out_of_bounds);
+    }
+
+    #[test]
+    fn test_extract_out_of_bounds() {
+        let content = "one\ntwo\n";
+
+        let (extracted, out_of_bounds) = range(2, Some(10)).extract(content);
+        let extracted = extracted.unwrap();
+        assert_eq!(extracted.content, "two");
+        assert_eq!((extracted.start_line, extracted.end_line), (2, 2));
+        assert!(out_of_bounds);
+    }
+
+    #[test]
+    fn test_extract_start_past_end() {
+        let content = "one\ntwo\nthree\nfour\n";
+
+        let (extracted, out_of_bounds) = range(5, None).extract(content);
+        assert!(extracted.is_none());
+        assert!(out_of_bounds);
+
+        let (extracted, out_of_bounds) = range(5, Some(8)).extract(content);
+        assert!(extracted.is_none());
+        assert!(out_of_bounds);
+
+        let (extracted, out_of_bounds) = range(1, None).extract("");
+        assert!(extracted.is_none());
+        assert!(out_of_bounds);
+    }
+
+    #[test]
+    fn test_extract_region() {
+        let content = "fn a() {}\n// ef:begin setup\nlet x = 1;\n// ef:begin inner\nlet y = 2;\n// ef:end inner\n// ef:end setup\n";
+
+        let extracted = extract_region(content, "setup", &["//"]).unwrap();
+        assert_eq!(extracted.content, "let x = 1;\nlet y = 2;");
+        assert_eq!((extracted.start_line, extracted.end_line), (3, 6));
+
+        let extracted = extract_region(content, "inner", &["//"]).unwrap();
+        assert_eq!(extracted.content, "let y = 2;");
+    }
+
+    #[test]
+    fn test_extract_region_with_other_comment_syntax() {
+        let content = "<!-- ef:begin intro -->\nHello\n<!-- ef:end intro -->";
+        let extracted = extract_region(content, "intro", &["<!--"]).unwrap();
+        assert_eq!(extracted.content, "Hello");
+
+        // 言語のコメント記号でなければマーカーとして扱わない
+        let content = "# ef:begin intro\nHello\n# ef:end intro";
+        assert_eq!(
+            extract_region(content, "intro", &["//"]),
+            Err(RegionError::NotFound)
+        );
+    }
+
+    #[test]
+    fn test_extract_region_unbalanced() {
+        assert!(matches!(
+            extract_region("// ef:begin a\nx", "a", &["//"]),
+            Err(RegionError::Unbalanced(_))
+        ));
+        assert!(matches!(
+            extract_region("x\n// ef:end a", "a", &["//"]),
+            Err(RegionError::Unbalanced(_))
+        ));
+        assert!(matches!(
+            extract_region("// ef:begin a\n// ef:begin a\n// ef:end a", "a", &["//"]),
+            Err(RegionError::Unbalanced(_))
+        ));
+        assert_eq!(
+            extract_region("// ef:begin b\n// ef:end b", "a", &["//"]),
+            Err(RegionError::NotFound)
+        );
+    }
+}
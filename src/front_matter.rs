@@ -0,0 +1,166 @@
+use crate::error::{Error, Result};
+use serde::Deserialize;
+use std::collections::BTreeMap;
+use std::path::PathBuf;
+
+const YAML_DELIMITER: &str = "---";
+const TOML_DELIMITER: &str = "+++";
+
+/// プロンプトテンプレート先頭のフロントマターで指定できる設定
+///
+/// `---`で囲むとYAML、`+++`で囲むとTOMLとして解釈する。
+/// `eftemplate`と`path_base`はテンプレートファイルのディレクトリを起点に解決する。
+#[derive(Debug, Default, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct FrontMatter {
+    /// 使用する.eftemplateファイル（指定時は`format`より優先）
+    pub eftemplate: Option<PathBuf>,
+    /// 組み込みの出力形式（`markdown`, `xml`, `plain`）
+    pub format: Option<String>,
+    /// 出力全体の推定トークン数の上限。超えた場合は警告を出す
+    pub token_budget: Option<usize>,
+    /// `{filePath}`を表示する際の基準ディレクトリ
+    pub path_base: Option<PathBuf>,
+    /// 展開対象から除外するglobパターン
+    pub ignore: Vec<String>,
+    /// テンプレートで`{{name}}`、.eftemplateで`{name}`として参照できる変数
+    pub variables: BTreeMap<String, String>,
+}
+
+/// テンプレートの内容をフロントマターと本文に分割する
+///
+/// 閉じられていないものや、キーと値の組として解釈できないもの（`---`の区切り線で囲んだ文章など）は
+/// 通常のテキストとして扱う。
+pub fn split(content: &str) -> Result<(FrontMatter, &str)> {
+    let Some((delimiter, rest)) = [YAML_DELIMITER, TOML_DELIMITER]
+        .into_iter()
+        .find_map(|delimiter| Some((delimiter, strip_delimiter_line(content, delimiter)?)))
+    else {
+        return Ok((FrontMatter::default(), content));
+    };
+
+    let mut offset = 0;
+    for line in rest.split_inclusive('\n') {
+        if line.trim_end() == delimiter {
+            let source = &rest[..offset];
+            let body = &rest[offset + line.len()..];
+            let front_matter = if delimiter == YAML_DELIMITER {
+                parse_yaml(source)?
+            } else {
+                parse_toml(source)?
+            };
+            return Ok(match front_matter {
+                Some(front_matter) => (front_matter, body),
+                None => (FrontMatter::default(), content),
+            });
+        }
+        offset += line.len();
+    }
+
+    Ok((FrontMatter::default(), content))
+}
+
+fn strip_delimiter_line<'a>(content: &'a str, delimiter: &str) -> Option<&'a str> {
+    let (first_line, rest) = content.split_once('\n')?;
+    (first_line.trim_end() == delimiter).then_some(rest)
+}
+
+/// マッピングでなければ`None`を返す
+fn parse_yaml(source: &str) -> Result<Option<FrontMatter>> {
+    let value = match serde_yaml::from_str(source) {
+        Ok(value @ serde_yaml::Value::Mapping(_)) => value,
+        // 空やコメントだけの場合
+        Ok(serde_yaml::Value::Null) => return Ok(Some(FrontMatter::default())),
+        _ => return Ok(None),
+    };
+    serde_yaml::from_value(value)
+        .map(Some)
+        .map_err(|e| Error::InvalidFrontMatter {
+            message: e.to_string(),
+        })
+}
+
+/// TOMLのテーブルとして解釈できなければ`None`を返す
+fn parse_toml(source: &str) -> Result<Option<FrontMatter>> {
+    let Ok(table) = toml::from_str::<toml::Table>(source) else {
+        return Ok(None);
+    };
+    table
+        .try_into()
+        .map(Some)
+        .map_err(|e| Error::InvalidFrontMatter {
+            message: e.to_string(),
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_without_front_matter() {
+        let (front_matter, body) = split("Hello\n#ef src/*.rs").unwrap();
+        assert_eq!(front_matter, FrontMatter::default());
+        assert_eq!(body, "Hello\n#ef src/*.rs");
+    }
+
+    #[test]
+    fn test_yaml_front_matter() {
+        let content = "---\nformat: xml\ntoken_budget: 1000\nignore:\n  - \"*.test.ts\"\nvariables:\n  project: ef\n---\nBody\n";
+        let (front_matter, body) = split(content).unwrap();
+
+        assert_eq!(front_matter.format.as_deref(), Some("xml"));
+        assert_eq!(front_matter.token_budget, Some(1000));
+        assert_eq!(front_matter.ignore, vec!["*.test.ts".to_string()]);
+        assert_eq!(front_matter.variables["project"], "ef");
+        assert_eq!(body, "Body\n");
+    }
+
+    #[test]
+    fn test_toml_front_matter() {
+        let content =
+            "+++\neftemplate = \"templates/review.eftemplate\"\npath_base = \"..\"\n+++\nBody";
+        let (front_matter, body) = split(content).unwrap();
+
+        assert_eq!(
+            front_matter.eftemplate,
+            Some(PathBuf::from("templates/review.eftemplate"))
+        );
+        assert_eq!(front_matter.path_base, Some(PathBuf::from("..")));
+        assert_eq!(body, "Body");
+    }
+
+    #[test]
+    fn test_unclosed_front_matter_is_text() {
+        let content = "---\nNot front matter";
+        let (front_matter, body) = split(content).unwrap();
+
+        assert_eq!(front_matter, FrontMatter::default());
+        assert_eq!(body, content);
+    }
+
+    #[test]
+    fn test_rules_around_text_are_not_front_matter() {
+        for content in [
+            "---\nReview the following code.\n---\nThanks",
+            "---\n- first\n- second\n---\n",
+            "+++\nReview the following code.\n+++\nThanks",
+        ] {
+            let (front_matter, body) = split(content).unwrap();
+            assert_eq!(front_matter, FrontMatter::default());
+            assert_eq!(body, content);
+        }
+    }
+
+    #[test]
+    fn test_invalid_front_matter() {
+        assert!(matches!(
+            split("---\nunknown_key: 1\n---\n"),
+            Err(Error::InvalidFrontMatter { .. })
+        ));
+        assert!(matches!(
+            split("+++\ntoken_budget = \"many\"\n+++\n"),
+            Err(Error::InvalidFrontMatter { .. })
+        ));
+    }
+}
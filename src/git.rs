@@ -0,0 +1,312 @@
+use crate::error::{Error, Result};
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+/// `git`コマンドを実行し、標準出力を返す
+///
+/// 終了コードが0以外の場合は標準エラー出力の内容で`Error::Git`にする。
+pub fn run(dir: &Path, args: &[String]) -> Result<String> {
+    let stdout = run_bytes(dir, args)?;
+    Ok(String::from_utf8_lossy(&stdout).into_owned())
+}
+
+fn run_bytes(dir: &Path, args: &[String]) -> Result<Vec<u8>> {
+    let output = Command::new("git")
+        .current_dir(dir)
+        // 非ASCIIのパスをエスケープさせない
+        .args(["-c", "core.quotePath=false"])
+        .args(args)
+        .output()
+        .map_err(|e| Error::Git {
+            command: args.join(" "),
+            message: e.to_string(),
+        })?;
+
+    if !output.status.success() {
+        return Err(Error::Git {
+            command: args.join(" "),
+            message: String::from_utf8_lossy(&output.stderr).trim().to_string(),
+        });
+    }
+
+    Ok(output.stdout)
+}
+
+/// `dir`を含むリポジトリのルートディレクトリを返す
+pub fn repository_root(dir: &Path) -> Result<PathBuf> {
+    let output = run(
+        dir,
+        &["rev-parse".to_string(), "--show-toplevel".to_string()],
+    )?;
+    Ok(PathBuf::from(output.trim_end_matches('\n')))
+}
+
+/// gitの状態によるファイルの絞り込み
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash, clap::ValueEnum)]
+pub enum Selection {
+    /// 絞り込まない
+    #[default]
+    #[value(help = "Every file on disk")]
+    All,
+    /// インデックスに登録されているファイルのみ
+    #[value(help = "Files tracked in the git index")]
+    TrackedOnly,
+    /// ステージした変更があるファイルのみ
+    #[value(help = "Files with staged changes")]
+    Staged,
+    /// HEADから変更されたファイルのみ（ステージしたものを含む）
+    #[value(help = "Files changed since HEAD (staged or not)")]
+    Modified,
+    /// 追跡されているファイルと、無視されない未追跡のファイル
+    #[value(help = "Tracked files plus untracked files that are not ignored")]
+    IncludeUntracked,
+}
+
+/// `selection`に該当するファイルをリポジトリのルートからの相対パスで返す（`All`なら`None`）
+pub fn selected_files(root: &Path, selection: Selection) -> Result<Option<Vec<PathBuf>>> {
+    let args: &[&str] = match selection {
+        Selection::All => return Ok(None),
+        Selection::TrackedOnly => &["ls-files", "-z"],
+        Selection::Staged => &["diff", "--cached", "--name-only", "-z", "--diff-filter=d"],
+        Selection::Modified => &["diff", "HEAD", "--name-only", "-z", "--diff-filter=d"],
+        Selection::IncludeUntracked => &[
+            "ls-files",
+            "-z",
+            "--cached",
+            "--others",
+            "--exclude-standard",
+        ],
+    };
+    let args = args.iter().map(|arg| arg.to_string()).collect::<Vec<_>>();
+
+    Ok(Some(
+        run(root, &args)?
+            .split('\0')
+            .filter(|path| !path.is_empty())
+            .map(PathBuf::from)
+            .collect(),
+    ))
+}
+
+/// リビジョンを指定したパス（`#ef git:HEAD~3:src/main.rs`）
+#[derive(Debug, Clone, PartialEq)]
+pub struct RevisionPath {
+    pub revision: String,
+    /// `dir`からの相対パス（globパターンも使える）
+    pub pattern: String,
+}
+
+/// リビジョン`revision`に存在するファイルを、`dir`からの相対パスで返す
+pub fn list_files(dir: &Path, revision: &str) -> Result<Vec<PathBuf>> {
+    let args = ["ls-tree", "-r", "--name-only", "-z", revision].map(String::from);
+    Ok(run(dir, &args)?
+        .split('\0')
+        .filter(|path| !path.is_empty())
+        .map(PathBuf::from)
+        .collect())
+}
+
+/// リビジョン`revision`でのファイルの内容を返す（`path`は`dir`からの相対パス）
+pub fn show(dir: &Path, revision: &str, path: &Path) -> Result<Vec<u8>> {
+    let object = format!("{}:./{}", revision, path.to_string_lossy());
+    run_bytes(dir, &["show".to_string(), object])
+}
+
+/// `#efdiff`で取得する差分の範囲
+#[derive(Debug, Default, PartialEq)]
+pub struct DiffOptions {
+    /// 比較するリビジョン（`main..feature`など）。省略時はHEAD
+    pub revisions: Option<String>,
+    pub pathspecs: Vec<String>,
+    /// 前後に含める行数（省略時はgitの設定に従う）
+    pub context: Option<usize>,
+    /// ワーキングツリーではなくステージした内容と比較するか
+    pub staged: bool,
+}
+
+/// 1ファイル分の差分
+#[derive(Debug, PartialEq)]
+pub struct FileDiff {
+    /// リポジトリのルートからの相対パス
+    pub path: PathBuf,
+    pub patch: String,
+}
+
+/// unified diff形式の差分をファイルごとに返す
+///
+/// パスは差分の本文からではなく`--name-status -z`の出力から取る
+/// （本文のパスは空白を含むと末尾にタブが付き、特殊な文字は引用符で囲まれるため）。
+pub fn diff(dir: &Path, options: &DiffOptions) -> Result<Vec<FileDiff>> {
+    let mut range = Vec::new();
+    if options.staged {
+        range.push("--cached".to_string());
+    }
+    range.push(
+        options
+            .revisions
+            .clone()
+            .unwrap_or_else(|| "HEAD".to_string()),
+    );
+    range.push("--".to_string());
+    range.extend(options.pathspecs.iter().cloned());
+
+    let mut args = vec![
+        "diff".to_string(),
+        "--no-color".to_string(),
+        "--no-ext-diff".to_string(),
+    ];
+    if let Some(context) = options.context {
+        args.push(format!("--unified={}", context));
+    }
+    args.extend(range.iter().cloned());
+    let patches = split_patches(&run(dir, &args)?);
+
+    let mut args = ["diff", "--name-status", "-z", "--no-ext-diff"]
+        .map(String::from)
+        .to_vec();
+    args.extend(range);
+    let paths = name_status_entries(&run(dir, &args)?);
+
+    // どちらも同じ順序でファイルを並べる
+    Ok(paths
+        .into_iter()
+        .zip(patches)
+        .map(|((_, path), patch)| FileDiff { path, patch })
+        .collect())
+}
+
+/// ベースのリビジョンから変更されたファイル（パスはリポジトリのルートからの相対パス）
+#[derive(Debug, Default, PartialEq)]
+pub struct ChangedFiles {
+    /// 追加・変更されたファイル（名前を変えたものは変更後のパス）
+    pub changed: Vec<PathBuf>,
+    pub deleted: Vec<PathBuf>,
+}
+
+/// `base`（省略時はHEAD）とワーキングツリーの間で変更されたファイルを返す
+///
+/// `include_untracked`を指定すると、追跡されていないファイル（無視されるものを除く）も含める。
+pub fn changed_files(
+    root: &Path,
+    base: Option<&str>,
+    include_untracked: bool,
+) -> Result<ChangedFiles> {
+    let args = [
+        "diff",
+        "--name-status",
+        "-z",
+        "--no-ext-diff",
+        base.unwrap_or("HEAD"),
+        "--",
+    ]
+    .map(String::from);
+    let mut files = parse_name_status(&run(root, &args)?);
+
+    if include_untracked {
+        let args = ["ls-files", "--others", "--exclude-standard", "-z"].map(String::from);
+        files.changed.extend(
+            run(root, &args)?
+                .split('\0')
+                .filter(|path| !path.is_empty())
+                .map(PathBuf::from),
+        );
+        files.changed.sort();
+        files.changed.dedup();
+    }
+
+    Ok(files)
+}
+
+/// `git diff --name-status -z`の出力を解釈する
+fn parse_name_status(output: &str) -> ChangedFiles {
+    let mut files = ChangedFiles::default();
+    for (status, path) in name_status_entries(output) {
+        if status.starts_with('D') {
+            files.deleted.push(path);
+        } else {
+            files.changed.push(path);
+        }
+    }
+    files
+}
+
+/// `git diff --name-status -z`の出力を、ステータスとパス（名前の変更は変更後のパス）の組にする
+fn name_status_entries(output: &str) -> Vec<(String, PathBuf)> {
+    let mut entries = Vec::new();
+    let mut fields = output.split('\0').filter(|field| !field.is_empty());
+    while let Some(status) = fields.next() {
+        // 名前の変更とコピーは変更前と変更後の2つのパスが続く
+        if status.starts_with(['R', 'C']) {
+            fields.next();
+        }
+        let Some(path) = fields.next() else {
+            break;
+        };
+        entries.push((status.to_string(), PathBuf::from(path)));
+    }
+    entries
+}
+
+/// `git diff`の出力を`diff --git`の行ごとに分ける
+fn split_patches(output: &str) -> Vec<String> {
+    let mut patches: Vec<String> = Vec::new();
+    for line in output.split_inclusive('\n') {
+        match patches.last_mut() {
+            Some(patch) if !line.starts_with("diff --git ") => patch.push_str(line),
+            _ => patches.push(line.to_string()),
+        }
+    }
+
+    patches
+        .into_iter()
+        .map(|patch| patch.trim_end_matches('\n').to_string())
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_name_status() {
+        let output = "M\0src/main.rs\0A\0docs/new file.md\0D\0old.txt\0R087\0a.rs\0b.rs\0";
+        assert_eq!(
+            parse_name_status(output),
+            ChangedFiles {
+                changed: vec![
+                    PathBuf::from("src/main.rs"),
+                    PathBuf::from("docs/new file.md"),
+                    PathBuf::from("b.rs"),
+                ],
+                deleted: vec![PathBuf::from("old.txt")],
+            }
+        );
+    }
+
+    #[test]
+    fn test_split_patches() {
+        let output = "\
+diff --git a/src/main.rs b/src/main.rs
+index 1111111..2222222 100644
+--- a/src/main.rs
++++ b/src/main.rs
+@@ -1 +1 @@
+-fn main() {}
++fn main() { run(); }
+diff --git a/old.txt b/old.txt
+deleted file mode 100644
+--- a/old.txt
++++ /dev/null
+@@ -1 +0,0 @@
+-old
+diff --git a/image.png b/image.png
+Binary files a/image.png and b/image.png differ
+";
+        let patches = split_patches(output);
+        assert_eq!(patches.len(), 3);
+        assert!(patches[0].starts_with("diff --git a/src/main.rs"));
+        assert!(patches[0].ends_with("+fn main() { run(); }"));
+        assert!(patches[1].starts_with("diff --git a/old.txt"));
+        assert!(patches[2].starts_with("diff --git a/image.png"));
+    }
+}
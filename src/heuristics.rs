@@ -0,0 +1,228 @@
+use once_cell::sync::Lazy;
+use regex::Regex;
+
+/// 1つの拡張子が複数の言語に対応する場合に、内容から言語を推定するための規則
+///
+/// github-linguistのheuristics.ymlに倣い、`rules`を上から順に評価して
+/// `patterns`のいずれかにマッチした最初の言語を採用する。
+/// `patterns`が空の規則はそれまでの規則にマッチしなかった場合のデフォルトになる。
+struct Heuristic {
+    extensions: &'static [&'static str],
+    rules: &'static [Rule],
+}
+
+struct Rule {
+    language: &'static str,
+    patterns: &'static [&'static str],
+}
+
+const OBJECTIVE_C: &str = r"^\s*(@(interface|class|protocol|property|end|synchronised|selector|implementation)\b|#import\s+.+\.h[\x22>])";
+
+const CPP: &[&str] = &[
+    r"^\s*#\s*include <(cstdint|string|vector|map|list|array|bitset|queue|stack|forward_list|unordered_map|unordered_set|(i|o|io)stream)>",
+    r"^\s*template\s*<",
+    r"^[ \t]*(class|(using[ \t]+)?namespace)\s+\w+",
+    r"^[ \t]*(private|public|protected):$",
+    r"std::\w+",
+    r"\b(nullptr|constexpr|static_cast|dynamic_cast|reinterpret_cast)\b",
+];
+
+const HEURISTICS: &[Heuristic] = &[
+    Heuristic {
+        extensions: &["h"],
+        rules: &[
+            Rule {
+                language: "objective-c",
+                patterns: &[OBJECTIVE_C],
+            },
+            Rule {
+                language: "c++",
+                patterns: CPP,
+            },
+            Rule {
+                language: "c",
+                patterns: &[],
+            },
+        ],
+    },
+    Heuristic {
+        extensions: &["m"],
+        rules: &[
+            Rule {
+                language: "objective-c",
+                patterns: &[OBJECTIVE_C],
+            },
+            Rule {
+                language: "mercury",
+                patterns: &[r":- module"],
+            },
+            Rule {
+                language: "matlab",
+                patterns: &[
+                    r"^\s*%",
+                    r"^\s*function\s+(\[[^\]]*\]|\w+)\s*=",
+                    r"^\s*end(function)?\s*$",
+                ],
+            },
+        ],
+    },
+    Heuristic {
+        extensions: &["pl"],
+        rules: &[
+            Rule {
+                language: "prolog",
+                patterns: &[r"^[^#]*:-"],
+            },
+            Rule {
+                language: "perl",
+                patterns: &[r"\buse\s+(?:strict\b|v?5\b)", r"^\s*(my|our|sub)\b"],
+            },
+            Rule {
+                language: "raku",
+                patterns: &[r"^\s*(?:use\s+v6\b|\bmodule\b|\b(?:my\s+)?class\b)"],
+            },
+        ],
+    },
+    Heuristic {
+        extensions: &["pm"],
+        rules: &[
+            Rule {
+                language: "perl",
+                patterns: &[r"\buse\s+(?:strict\b|v?5\b)", r"^\s*package\s+[\w:]+\s*;"],
+            },
+            Rule {
+                language: "raku",
+                patterns: &[r"^\s*(?:use\s+v6\b|\bmodule\b|\b(?:my\s+)?class\b)"],
+            },
+        ],
+    },
+    Heuristic {
+        extensions: &["ts"],
+        rules: &[
+            // Qt Linguistの翻訳ファイル
+            Rule {
+                language: "xml",
+                patterns: &[r"<TS\b"],
+            },
+            Rule {
+                language: "typescript",
+                patterns: &[],
+            },
+        ],
+    },
+];
+
+struct CompiledRule {
+    language: &'static str,
+    patterns: Vec<Regex>,
+}
+
+static COMPILED: Lazy<Vec<(&'static [&'static str], Vec<CompiledRule>)>> = Lazy::new(|| {
+    HEURISTICS
+        .iter()
+        .map(|heuristic| {
+            let rules = heuristic
+                .rules
+                .iter()
+                .map(|rule| CompiledRule {
+                    language: rule.language,
+                    patterns: rule
+                        .patterns
+                        .iter()
+                        .map(|pattern| Regex::new(&format!("(?m){}", pattern)).unwrap())
+                        .collect(),
+                })
+                .collect();
+            (heuristic.extensions, rules)
+        })
+        .collect()
+});
+
+/// 曖昧な拡張子について、ファイル内容から言語を推定する
+///
+/// 対象外の拡張子や、どの規則にもマッチしなかった場合は`None`を返す。
+pub fn disambiguate(extension: &str, content: &str) -> Option<&'static str> {
+    let extension = extension.to_lowercase();
+    let (_, rules) = COMPILED
+        .iter()
+        .find(|(extensions, _)| extensions.contains(&extension.as_str()))?;
+
+    rules
+        .iter()
+        .find(|rule| {
+            rule.patterns.is_empty() || rule.patterns.iter().any(|regex| regex.is_match(content))
+        })
+        .map(|rule| rule.language)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_c_headers() {
+        assert_eq!(
+            disambiguate("h", "#include <stdio.h>\nint f(void);"),
+            Some("c")
+        );
+        assert_eq!(
+            disambiguate("h", "#include <vector>\nstd::vector<int> f();"),
+            Some("c++")
+        );
+        assert_eq!(
+            disambiguate("h", "namespace foo {\nclass Bar {};\n}"),
+            Some("c++")
+        );
+        assert_eq!(
+            disambiguate(
+                "h",
+                "#import <Foundation/Foundation.h>\n@interface Foo : NSObject\n@end"
+            ),
+            Some("objective-c")
+        );
+    }
+
+    #[test]
+    fn test_m_files() {
+        assert_eq!(
+            disambiguate("m", "@implementation Foo\n@end"),
+            Some("objective-c")
+        );
+        assert_eq!(
+            disambiguate("m", ":- module hello.\n:- interface."),
+            Some("mercury")
+        );
+        assert_eq!(
+            disambiguate("m", "% compute\nfunction y = f(x)\n  y = x;\nend"),
+            Some("matlab")
+        );
+        assert_eq!(disambiguate("m", "???"), None);
+    }
+
+    #[test]
+    fn test_perl_and_prolog() {
+        assert_eq!(disambiguate("pl", "use strict;\nmy $x = 1;"), Some("perl"));
+        assert_eq!(
+            disambiguate("pl", "parent(tom, bob).\nancestor(X, Y) :- parent(X, Y)."),
+            Some("prolog")
+        );
+        assert_eq!(disambiguate("pl", "use v6;\nsay 'hi';"), Some("raku"));
+    }
+
+    #[test]
+    fn test_typescript_and_qt_translations() {
+        assert_eq!(
+            disambiguate("ts", "<?xml version=\"1.0\"?>\n<TS version=\"2.1\">"),
+            Some("xml")
+        );
+        assert_eq!(
+            disambiguate("ts", "export const x: number = 1;"),
+            Some("typescript")
+        );
+    }
+
+    #[test]
+    fn test_unambiguous_extension() {
+        assert_eq!(disambiguate("rs", "fn main() {}"), None);
+    }
+}
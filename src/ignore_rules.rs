@@ -0,0 +1,158 @@
+use crate::eftemplate::find_upwards;
+use crate::error::Result;
+use ignore::gitignore::{gitconfig_excludes_path, Gitignore, GitignoreBuilder};
+use ignore::Match;
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+
+/// ef専用の除外ファイル（書式は.gitignoreと同じ）
+const EFIGNORE: &str = ".efignore";
+
+/// .gitignoreなどの除外ファイルによる判定
+///
+/// 次の順に調べ、最初に決まった結果（除外、または`!`による除外の取り消し）を使う。
+///
+/// 1. `.efignore`（テンプレートのディレクトリから親を順に探し、最初に見つかったもの）
+/// 2. 対象のディレクトリから上に向かって、リポジトリのルートまでの各`.gitignore`
+/// 3. `.git/info/exclude`と、gitの設定による全体の除外ファイル（`core.excludesFile`）
+///
+/// .gitignoreはgitのリポジトリの中でだけ使い、`.git`ディレクトリは常に除外する。
+pub struct IgnoreRules {
+    efignore: Option<Gitignore>,
+    /// ディレクトリごとの.gitignore（ないディレクトリは`None`）
+    gitignores: RefCell<HashMap<PathBuf, Option<Gitignore>>>,
+    /// リポジトリのルートごとの`.git/info/exclude`と全体の除外ファイル
+    excludes: RefCell<HashMap<PathBuf, Gitignore>>,
+}
+
+impl IgnoreRules {
+    /// `start_path`から`.efignore`を探して読み込む
+    pub fn new<P: AsRef<Path>>(start_path: P) -> Result<Self> {
+        let efignore = find_upwards(start_path, EFIGNORE)?.map(|path| Gitignore::new(path).0);
+
+        Ok(Self {
+            efignore,
+            gitignores: RefCell::new(HashMap::new()),
+            excludes: RefCell::new(HashMap::new()),
+        })
+    }
+
+    /// 絶対パス`path`が除外されるか
+    pub fn is_ignored(&self, path: &Path, is_dir: bool) -> bool {
+        if path
+            .components()
+            .any(|component| component.as_os_str() == ".git")
+        {
+            return true;
+        }
+
+        if let Some(efignore) = &self.efignore {
+            if path.starts_with(efignore.path()) {
+                match efignore.matched_path_or_any_parents(path, is_dir) {
+                    Match::Ignore(_) => return true,
+                    Match::Whitelist(_) => return false,
+                    Match::None => {}
+                }
+            }
+        }
+
+        let Some(root) = repository_root(path) else {
+            return false;
+        };
+
+        for dir in path.ancestors().skip(1) {
+            if !dir.starts_with(&root) {
+                break;
+            }
+            let mut gitignores = self.gitignores.borrow_mut();
+            let gitignore = gitignores
+                .entry(dir.to_path_buf())
+                .or_insert_with(|| load(&dir.join(".gitignore")));
+            if let Some(decided) = decide(gitignore.as_ref(), path, is_dir) {
+                return decided;
+            }
+        }
+
+        let mut excludes = self.excludes.borrow_mut();
+        let excludes = excludes
+            .entry(root.clone())
+            .or_insert_with(|| repository_excludes(&root));
+        decide(Some(excludes), path, is_dir).unwrap_or(false)
+    }
+}
+
+fn decide(gitignore: Option<&Gitignore>, path: &Path, is_dir: bool) -> Option<bool> {
+    match gitignore?.matched_path_or_any_parents(path, is_dir) {
+        Match::Ignore(_) => Some(true),
+        Match::Whitelist(_) => Some(false),
+        Match::None => None,
+    }
+}
+
+fn load(path: &Path) -> Option<Gitignore> {
+    path.is_file().then(|| Gitignore::new(path).0)
+}
+
+/// `path`を含むgitリポジトリのルート（`.git`のあるディレクトリ）
+pub(crate) fn repository_root(path: &Path) -> Option<PathBuf> {
+    path.ancestors()
+        .skip(1)
+        .find(|dir| dir.join(".git").exists())
+        .map(Path::to_path_buf)
+}
+
+/// `.git/info/exclude`と`core.excludesFile`をまとめた除外ルール
+fn repository_excludes(root: &Path) -> Gitignore {
+    let mut builder = GitignoreBuilder::new(root);
+    let info_exclude = root.join(".git/info/exclude");
+    if info_exclude.is_file() {
+        builder.add(info_exclude);
+    }
+    if let Some(global) = gitconfig_excludes_path().filter(|path| path.is_file()) {
+        builder.add(global);
+    }
+    builder.build().unwrap_or_else(|_| Gitignore::empty())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+
+    #[test]
+    fn test_ignore_rules() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path().canonicalize().unwrap();
+        fs::create_dir_all(root.join(".git/info")).unwrap();
+        fs::create_dir_all(root.join("src/generated")).unwrap();
+        fs::create_dir_all(root.join("target/debug")).unwrap();
+        fs::write(root.join(".gitignore"), "target/\n*.log\n").unwrap();
+        fs::write(root.join("src/.gitignore"), "generated/\n!keep.log\n").unwrap();
+        fs::write(root.join(".git/info/exclude"), "local.txt\n").unwrap();
+        fs::write(root.join(".efignore"), "docs/\n").unwrap();
+
+        let rules = IgnoreRules::new(&root).unwrap();
+        let ignored = |path: &str, is_dir: bool| rules.is_ignored(&root.join(path), is_dir);
+
+        assert!(ignored("target", true));
+        assert!(ignored("target/debug/ef", false));
+        assert!(ignored("debug.log", false));
+        assert!(ignored("src/generated/api.rs", false));
+        assert!(!ignored("src/keep.log", false));
+        assert!(ignored("local.txt", false));
+        assert!(ignored("docs/guide.md", false));
+        assert!(ignored(".git/HEAD", false));
+        assert!(!ignored("src/main.rs", false));
+    }
+
+    #[test]
+    fn test_gitignore_outside_repository() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path().canonicalize().unwrap();
+        fs::write(root.join(".gitignore"), "*.log\n").unwrap();
+
+        let rules = IgnoreRules::new(&root).unwrap();
+        assert!(!rules.is_ignored(&root.join("debug.log"), false));
+    }
+}
@@ -0,0 +1,387 @@
+use crate::eftemplate::find_upwards;
+use crate::error::{Error, Result};
+use crate::heuristics;
+use once_cell::sync::Lazy;
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::Path;
+
+/// ユーザーが言語マッピングを上書きするためのファイル名
+pub const OVERRIDE_FILE_NAME: &str = ".eflanguages";
+
+/// モードラインを探す行数（vimの`modelines`のデフォルト値に合わせている）
+const MODELINE_SEARCH_LINES: usize = 5;
+
+const BUILTIN_MAPPINGS: &str = include_str!("../assets/language_mappings.ron");
+
+static BUILTIN: Lazy<LanguageMappings> = Lazy::new(|| {
+    LanguageMappings::parse(BUILTIN_MAPPINGS)
+        .expect("assets/language_mappings.ron must be valid RON")
+});
+
+static VIM_MODELINE: Lazy<Regex> = Lazy::new(|| {
+    Regex::new(r"(?:^|\s)(?:vi|vim|ex)(?:[<=>]?\d+)?:.*?\b(?:ft|filetype|syntax)=([\w+#.-]+)")
+        .unwrap()
+});
+
+static EMACS_MODELINE: Lazy<Regex> = Lazy::new(|| {
+    Regex::new(r"-\*-\s*(?:(?:.*?;)?\s*mode:\s*([\w+#.-]+)[^*]*|([\w+#.-]+)\s*)-\*-").unwrap()
+});
+
+/// 言語マッピングファイル（assets/language_mappings.ron, `.eflanguages`）の内容
+#[derive(Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct LanguageMappings {
+    /// 拡張子（先頭の`.`なし） → 言語識別子
+    pub extensions: BTreeMap<String, String>,
+    /// ファイル名の完全一致 → 言語識別子
+    pub filenames: BTreeMap<String, String>,
+    /// shebangのインタプリタ名 → 言語識別子
+    pub interpreters: BTreeMap<String, String>,
+}
+
+impl LanguageMappings {
+    pub fn parse(content: &str) -> std::result::Result<Self, ron::error::SpannedError> {
+        ron::from_str(content)
+    }
+}
+
+/// ファイルの言語識別子を解決する
+///
+/// 組み込みのマッピング（assets/language_mappings.ron）に加えて、
+/// `.eflanguages` ファイルによる上書きをサポートする。
+///
+/// 判定は以下の優先順位で行う：
+/// 1. ファイル名の完全一致（`Dockerfile`, `CMakeLists.txt`, `.bashrc`など）
+/// 2. 1行目のshebang（`#!/usr/bin/env python3`など）
+/// 3. 先頭・末尾の行のモードライン（`vim: ft=python`, `-*- mode: ruby -*-`など）
+/// 4. 拡張子（長い拡張子を優先）
+///
+/// `.h`や`.pl`のように複数の言語で使われる拡張子は、`.eflanguages`で明示的に
+/// 指定されていない限り、内容に基づくヒューリスティクス（[`heuristics`]）で判定する。
+///
+/// [`heuristics`]: crate::heuristics
+#[derive(Default)]
+pub struct Languages {
+    overrides: LanguageMappings,
+}
+
+impl Languages {
+    pub fn find_and_load<P: AsRef<Path>>(start_path: P) -> Result<Self> {
+        match find_upwards(start_path, OVERRIDE_FILE_NAME)? {
+            Some(path) => Self::from_file(path),
+            None => Ok(Self::default()),
+        }
+    }
+
+    pub fn from_file<P: AsRef<Path>>(path: P) -> Result<Self> {
+        let path = path.as_ref();
+        let content = fs::read_to_string(path)?;
+        let overrides =
+            LanguageMappings::parse(&content).map_err(|e| Error::InvalidLanguageMappings {
+                path: path.to_path_buf(),
+                source: Box::new(e),
+            })?;
+
+        Ok(Self { overrides })
+    }
+
+    pub fn detect(&self, path: &Path, content: &str) -> Option<String> {
+        let file_name = path.file_name()?.to_str()?;
+
+        self.detect_by_filename(file_name)
+            .or_else(|| self.detect_by_shebang(content))
+            .or_else(|| self.detect_by_modeline(content))
+            .or_else(|| self.detect_by_extension(file_name, content))
+            .map(str::to_string)
+    }
+
+    fn detect_by_filename(&self, file_name: &str) -> Option<&str> {
+        self.mappings()
+            .find_map(|mappings| mappings.filenames.get(file_name))
+            .map(String::as_str)
+    }
+
+    fn detect_by_shebang(&self, content: &str) -> Option<&str> {
+        let interpreter = parse_shebang(content.lines().next()?)?;
+        self.lookup_interpreter(&interpreter)
+    }
+
+    fn detect_by_modeline(&self, content: &str) -> Option<&str> {
+        let lines = content.lines().collect::<Vec<_>>();
+        let head = lines.iter().take(MODELINE_SEARCH_LINES);
+        let tail = lines
+            .iter()
+            .skip(MODELINE_SEARCH_LINES)
+            .rev()
+            .take(MODELINE_SEARCH_LINES);
+
+        let mode = head.chain(tail).find_map(|line| parse_modeline(line))?;
+
+        // モードラインの値は`sh`や`cpp`のようなvimのfiletype名であることが多いので、
+        // インタプリタ名・拡張子として解釈し、それでも駄目なら既知の言語識別子と照合する
+        self.lookup_interpreter(&mode)
+            .or_else(|| self.lookup_extension(&mode))
+            .or_else(|| self.known_language(&mode))
+    }
+
+    fn detect_by_extension(&self, file_name: &str, content: &str) -> Option<&str> {
+        // `foo.d.ts` なら "d.ts" → "ts" の順に、長い拡張子を優先して探す
+        for (i, _) in file_name.match_indices('.') {
+            let extension = &file_name[i + 1..];
+            if extension.is_empty() {
+                continue;
+            }
+
+            let language = lookup_extension_in(&self.overrides, extension)
+                .or_else(|| heuristics::disambiguate(extension, content))
+                .or_else(|| lookup_extension_in(&BUILTIN, extension));
+            if language.is_some() {
+                return language;
+            }
+        }
+
+        None
+    }
+
+    fn lookup_extension(&self, extension: &str) -> Option<&str> {
+        self.mappings()
+            .find_map(|mappings| lookup_extension_in(mappings, extension))
+    }
+
+    fn lookup_interpreter(&self, interpreter: &str) -> Option<&str> {
+        // `python3.11` → `python` のようにバージョン番号を削って探す
+        let mut name = interpreter;
+        loop {
+            if let Some(language) = self
+                .mappings()
+                .find_map(|mappings| mappings.interpreters.get(name))
+            {
+                return Some(language);
+            }
+
+            let trimmed = name
+                .trim_end_matches(|c: char| c.is_ascii_digit() || c == '.')
+                .trim_end_matches('-');
+            if trimmed == name || trimmed.is_empty() {
+                return None;
+            }
+            name = trimmed;
+        }
+    }
+
+    fn known_language(&self, name: &str) -> Option<&str> {
+        let name = name.to_lowercase().replace('-', " ");
+        self.mappings().find_map(|mappings| {
+            [
+                &mappings.extensions,
+                &mappings.filenames,
+                &mappings.interpreters,
+            ]
+            .into_iter()
+            .flat_map(|map| map.values())
+            .find(|language| **language == name)
+            .map(String::as_str)
+        })
+    }
+
+    fn mappings(&self) -> impl Iterator<Item = &LanguageMappings> {
+        [&self.overrides, &*BUILTIN].into_iter()
+    }
+}
+
+fn lookup_extension_in<'a>(mappings: &'a LanguageMappings, extension: &str) -> Option<&'a str> {
+    mappings
+        .extensions
+        .get(extension)
+        .or_else(|| mappings.extensions.get(&extension.to_lowercase()))
+        .map(String::as_str)
+}
+
+/// shebang行からインタプリタ名を取り出す
+///
+/// `#!/usr/bin/env -S deno run` のような`env`経由の指定にも対応する。
+fn parse_shebang(line: &str) -> Option<String> {
+    let command = line.strip_prefix("#!")?.trim();
+    let mut words = command.split_whitespace();
+    let program = words.next()?.rsplit('/').next()?;
+
+    let interpreter = if program == "env" {
+        words.find(|word| !word.starts_with('-') && !word.contains('='))?
+    } else {
+        program
+    };
+
+    Some(interpreter.to_string())
+}
+
+fn parse_modeline(line: &str) -> Option<String> {
+    if let Some(captures) = VIM_MODELINE.captures(line) {
+        return Some(captures[1].to_string());
+    }
+
+    EMACS_MODELINE
+        .captures(line)
+        .and_then(|captures| captures.get(1).or_else(|| captures.get(2)))
+        .map(|m| m.as_str().to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    fn detect(path: &str, content: &str) -> Option<String> {
+        Languages::default().detect(Path::new(path), content)
+    }
+
+    #[test]
+    fn test_builtin_mappings() {
+        assert_eq!(detect("src/main.rs", "").as_deref(), Some("rust"));
+        assert_eq!(detect("README.md", "").as_deref(), Some("markdown"));
+        assert_eq!(detect("App.TSX", "").as_deref(), Some("typescript"));
+        assert_eq!(detect("notes.unknown", ""), None);
+    }
+
+    #[test]
+    fn test_compound_extension() {
+        assert_eq!(detect("prog.8xp.txt", "").as_deref(), Some("ti program"));
+        assert_eq!(detect("notes.txt", "").as_deref(), Some("plaintext"));
+    }
+
+    #[test]
+    fn test_filename_mappings() {
+        assert_eq!(detect("Dockerfile", "").as_deref(), Some("dockerfile"));
+        assert_eq!(detect("path/to/Makefile", "").as_deref(), Some("makefile"));
+        assert_eq!(detect("Justfile", "").as_deref(), Some("just"));
+        assert_eq!(detect(".bashrc", "").as_deref(), Some("shell"));
+        // 拡張子（.txt）よりもファイル名が優先される
+        assert_eq!(detect("CMakeLists.txt", "").as_deref(), Some("cmake"));
+    }
+
+    #[test]
+    fn test_shebang() {
+        assert_eq!(
+            detect("script", "#!/usr/bin/env python3\nprint(1)").as_deref(),
+            Some("python")
+        );
+        assert_eq!(
+            detect("run", "#!/usr/bin/env -S deno run --allow-net").as_deref(),
+            Some("typescript")
+        );
+        assert_eq!(
+            detect("build", "#!/bin/bash -e\n").as_deref(),
+            Some("shell")
+        );
+        assert_eq!(
+            detect("tool", "#!/usr/local/bin/python3.11").as_deref(),
+            Some("python")
+        );
+        // shebangは拡張子よりも優先される
+        assert_eq!(detect("hook.txt", "#!/bin/sh\n").as_deref(), Some("shell"));
+    }
+
+    #[test]
+    fn test_modeline() {
+        assert_eq!(
+            detect("config", "# vim: set ft=python :\nx = 1").as_deref(),
+            Some("python")
+        );
+        assert_eq!(
+            detect("config", "x = 1\n\n\n\n\n\n\n# vim: ft=sh").as_deref(),
+            Some("shell")
+        );
+        assert_eq!(
+            detect("init", ";; -*- mode: emacs-lisp; coding: utf-8 -*-").as_deref(),
+            Some("emacs lisp")
+        );
+        assert_eq!(detect("x", "// -*- C++ -*-").as_deref(), Some("c++"));
+    }
+
+    #[test]
+    fn test_ambiguous_extensions() {
+        assert_eq!(
+            detect("stdio.h", "int puts(const char *);").as_deref(),
+            Some("c")
+        );
+        assert_eq!(
+            detect("list.h", "template <typename T>\nclass List {};").as_deref(),
+            Some("c++")
+        );
+        assert_eq!(
+            detect("script.pl", "use strict;\nprint 1;").as_deref(),
+            Some("perl")
+        );
+        assert_eq!(
+            detect("app_ja.ts", "<!DOCTYPE TS>\n<TS version=\"2.1\">").as_deref(),
+            Some("xml")
+        );
+        assert_eq!(
+            detect("app.ts", "let x = 1;").as_deref(),
+            Some("typescript")
+        );
+    }
+
+    #[test]
+    fn test_override_precedes_heuristics() {
+        let languages = Languages {
+            overrides: LanguageMappings {
+                extensions: BTreeMap::from([("h".to_string(), "c++".to_string())]),
+                ..Default::default()
+            },
+        };
+        assert_eq!(
+            languages
+                .detect(Path::new("a.h"), "int f(void);")
+                .as_deref(),
+            Some("c++")
+        );
+    }
+
+    #[test]
+    fn test_filename_precedes_shebang() {
+        assert_eq!(
+            detect("Makefile", "#!/usr/bin/make -f\n").as_deref(),
+            Some("makefile")
+        );
+        assert_eq!(
+            detect("PKGBUILD", "#!/usr/bin/env python\n").as_deref(),
+            Some("shell")
+        );
+    }
+
+    #[test]
+    fn test_override_file() {
+        let temp_dir = tempdir().unwrap();
+        fs::create_dir_all(temp_dir.path().join("sub")).unwrap();
+        fs::write(
+            temp_dir.path().join(OVERRIDE_FILE_NAME),
+            r#"(
+                extensions: { "rs": "rs", "foo": "bar" },
+                filenames: { "Taskfile": "yaml" },
+            )"#,
+        )
+        .unwrap();
+
+        let languages = Languages::find_and_load(temp_dir.path().join("sub")).unwrap();
+        let detect = |path: &str| languages.detect(Path::new(path), "");
+        assert_eq!(detect("main.rs").as_deref(), Some("rs"));
+        assert_eq!(detect("x.foo").as_deref(), Some("bar"));
+        assert_eq!(detect("x.py").as_deref(), Some("python"));
+        assert_eq!(detect("Taskfile").as_deref(), Some("yaml"));
+    }
+
+    #[test]
+    fn test_invalid_override_file() {
+        let temp_dir = tempdir().unwrap();
+        let path = temp_dir.path().join(OVERRIDE_FILE_NAME);
+        fs::write(&path, "not ron").unwrap();
+
+        assert!(matches!(
+            Languages::from_file(&path),
+            Err(Error::InvalidLanguageMappings { .. })
+        ));
+    }
+}
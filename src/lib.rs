@@ -1,11 +1,25 @@
 use clap::Parser;
 
+mod arguments;
+mod classify;
 pub mod cli;
+mod command;
+mod comment;
+mod dependency;
 pub mod eftemplate;
 pub mod error;
 mod executor;
+pub mod extract;
+pub mod front_matter;
+mod git;
+mod heuristics;
+mod ignore_rules;
+pub mod language;
 pub mod path_resolver;
+mod rust_module;
+mod symbol;
 pub mod template;
+mod tree;
 pub mod warning;
 
 #[doc(hidden)]
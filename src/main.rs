@@ -1,9 +1,23 @@
+mod arguments;
+mod classify;
 mod cli;
+mod command;
+mod comment;
+mod dependency;
 mod eftemplate;
 mod error;
 mod executor;
+mod extract;
+mod front_matter;
+mod git;
+mod heuristics;
+mod ignore_rules;
+mod language;
 mod path_resolver;
+mod rust_module;
+mod symbol;
 mod template;
+mod tree;
 mod warning;
 
 use cli::parse_cli;
@@ -1,12 +1,90 @@
+use crate::arguments;
+use crate::classify::{Classifier, FileClass};
+use crate::dependency::{self, Resolution, SourceKind, TsConfig};
 use crate::error::{Error, Result};
+use crate::git::{self, Selection};
+use crate::ignore_rules::IgnoreRules;
+use crate::rust_module::{Module, SourceFile};
+use crate::tree::TreeEntry;
 use crate::warning::{Warning, Warnings};
-use glob::glob;
+use glob::{glob, MatchOptions, Pattern};
 use regex::Regex;
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet, VecDeque};
 use std::path::{Path, PathBuf};
 use std::{env, fs, io};
 
+const IGNORE_MATCH_OPTIONS: MatchOptions = MatchOptions {
+    case_sensitive: true,
+    require_literal_separator: true,
+    require_literal_leading_dot: false,
+};
+
+/// 展開対象から除外するためのglobパターン
+///
+/// `/`を含まないパターン（`*.test.ts`, `node_modules`）はパスのいずれかの要素に、
+/// `/`を含むパターン（`src/generated/**`）はbase_dirからの相対パスとその親ディレクトリにマッチする。
+/// `{a,b}`形式の選択肢は展開してからコンパイルする。
+struct IgnorePattern {
+    patterns: Vec<Pattern>,
+    match_components: bool,
+}
+
+impl IgnorePattern {
+    fn new(pattern: &str) -> Result<Self> {
+        let trimmed = pattern.trim_end_matches('/');
+        let match_components = !trimmed.contains('/');
+        let patterns = arguments::expand_braces(trimmed.trim_start_matches('/'))
+            .iter()
+            .map(|expanded| {
+                Pattern::new(expanded).map_err(|e| Error::InvalidGlobPattern {
+                    pattern: pattern.to_string(),
+                    source: e,
+                })
+            })
+            .collect::<Result<Vec<_>>>()?;
+
+        Ok(Self {
+            patterns,
+            match_components,
+        })
+    }
+
+    fn matches(&self, relative_path: &Path) -> bool {
+        self.patterns.iter().any(|pattern| {
+            if self.match_components {
+                relative_path.components().any(|component| {
+                    pattern.matches_with(
+                        &component.as_os_str().to_string_lossy(),
+                        IGNORE_MATCH_OPTIONS,
+                    )
+                })
+            } else {
+                relative_path.ancestors().any(|ancestor| {
+                    !ancestor.as_os_str().is_empty()
+                        && pattern.matches_path_with(ancestor, IGNORE_MATCH_OPTIONS)
+                })
+            }
+        })
+    }
+}
+
+/// `#ef`・`#efr`の引数で絞り込みのモードを指定するオプション（`select=tracked-only`）
+const SELECT_OPTION: &str = "select=";
+/// `#ef`・`#efr`の引数で、デフォルトでは除外する種類のファイルを含めるオプション（`include=generated,vendored`）
+const INCLUDE_OPTION: &str = "include=";
+
 pub struct PathResolver {
     base_dir: PathBuf,
+    ignore_patterns: Vec<IgnorePattern>,
+    /// .gitignoreと.efignoreによる除外（無効にした場合は`None`）
+    ignore_rules: Option<IgnoreRules>,
+    /// 指示子で指定しなかった場合の絞り込み
+    selection: Selection,
+    /// 絞り込みのモードごとの対象ファイル（絶対パス）
+    selected: HashMap<Selection, BTreeSet<PathBuf>>,
+    classifier: Classifier,
+    /// 生成されたファイルなどとして除外したファイル（base_dirからの相対パス）
+    skipped: BTreeMap<FileClass, BTreeSet<PathBuf>>,
     warnings: Warnings,
 }
 
@@ -16,11 +94,139 @@ impl PathResolver {
 
         Ok(Self {
             base_dir,
+            ignore_patterns: Vec::new(),
+            ignore_rules: None,
+            selection: Selection::All,
+            selected: HashMap::new(),
+            classifier: Classifier::new(),
+            skipped: BTreeMap::new(),
             warnings: Warnings::new(),
         })
     }
 
-    pub fn resolve_glob(&mut self, pattern: &str) -> Result<Vec<PathBuf>> {
+    /// .gitignore（入れ子のもの、`.git/info/exclude`、全体の除外ファイルを含む）と、
+    /// `start_path`から親を順に探した.efignoreによる除外を有効にする
+    pub fn use_ignore_files<P: AsRef<Path>>(&mut self, start_path: P) -> Result<()> {
+        self.ignore_rules = Some(IgnoreRules::new(start_path)?);
+        Ok(())
+    }
+
+    /// gitの状態による絞り込みを設定する（`select=`を指定した指示子ではそちらを優先する）
+    pub fn set_selection(&mut self, selection: Selection) {
+        self.selection = selection;
+    }
+
+    /// 以降の解決で除外するglobパターンを追加する
+    pub fn add_ignore_patterns<S: AsRef<str>>(&mut self, patterns: &[S]) -> Result<()> {
+        for pattern in patterns {
+            self.ignore_patterns
+                .push(IgnorePattern::new(pattern.as_ref())?);
+        }
+        Ok(())
+    }
+
+    /// `#efx`の引数（空白区切りの複数パターン）を、以降の解決で除外するパターンとして追加する
+    pub fn add_exclude_argument(&mut self, argument: &str) -> Result<()> {
+        self.add_ignore_patterns(&arguments::split(argument)?)
+    }
+
+    /// globパターンにマッチするファイルを返す
+    ///
+    /// 引数は空白区切りで複数のパターンを指定でき（空白を含むパスは引用符で囲む）、
+    /// `src/{api,db}/*.rs`のような波括弧による選択肢も展開する。
+    /// 結果はパターンの記述順に並べ、重複は取り除く。
+    /// `!`で始まる項（`src/**/*.ts !*.test.ts`）はこの指示子だけに適用される除外パターンとして扱う。
+    /// `select=<mode>`の項でこの指示子だけの絞り込みを指定できる。
+    ///
+    /// ワイルドカードを含むパターンでは、生成されたファイル・外部のファイル・ドキュメントを除外する。
+    /// `include=<kinds>`の項で、この指示子では除外しない種類を指定できる。
+    pub fn resolve_glob(&mut self, argument: &str) -> Result<Vec<PathBuf>> {
+        let mut select_terms = Vec::new();
+        let mut include_terms = Vec::new();
+        let mut terms = Vec::new();
+        for term in arguments::split(argument)? {
+            if let Some(value) = term.strip_prefix(SELECT_OPTION) {
+                select_terms.push(value.to_string());
+            } else if let Some(value) = term.strip_prefix(INCLUDE_OPTION) {
+                include_terms.push(value.to_string());
+            } else {
+                terms.push(term);
+            }
+        }
+        let selection = match select_terms.as_slice() {
+            [] => self.selection,
+            [value] => parse_selection(argument, value)?,
+            _ => return Err(invalid_option(argument, "expected a single `select=`")),
+        };
+        let included = match include_terms.as_slice() {
+            [] => Vec::new(),
+            [value] => parse_included(argument, value)?,
+            _ => return Err(invalid_option(argument, "expected a single `include=`")),
+        };
+
+        if terms.iter().all(|term| term.starts_with('!')) {
+            self.warnings.push(Warning::FileNotFound {
+                path: PathBuf::from(argument),
+            });
+            return Ok(Vec::new());
+        }
+
+        self.resolve_glob_terms_with(&terms, selection, &included)
+    }
+
+    /// 引数を分割済みのglobパターン（`!`で始まるものは除外パターン）にマッチするファイルを返す
+    pub fn resolve_glob_terms(&mut self, terms: &[String]) -> Result<Vec<PathBuf>> {
+        self.resolve_glob_terms_with(terms, self.selection, &[])
+    }
+
+    fn resolve_glob_terms_with(
+        &mut self,
+        terms: &[String],
+        selection: Selection,
+        included: &[FileClass],
+    ) -> Result<Vec<PathBuf>> {
+        let (excludes, includes): (Vec<&String>, Vec<&String>) =
+            terms.iter().partition(|term| term.starts_with('!'));
+        let excludes = excludes
+            .iter()
+            .map(|term| &term[1..])
+            .filter(|term| !term.is_empty())
+            .map(IgnorePattern::new)
+            .collect::<Result<Vec<_>>>()?;
+
+        let mut result = Vec::new();
+        let mut seen = HashSet::new();
+
+        for include in &includes {
+            let mut found = false;
+
+            for pattern in arguments::expand_braces(include) {
+                let classify_root = classify_root(&pattern)?;
+                for path in self.glob_files(&pattern, selection)? {
+                    found = true;
+                    let is_skipped = classify_root
+                        .as_ref()
+                        .is_some_and(|root| self.skip_classified(&path, root, included));
+                    if !self.matches_any(&excludes, &path)
+                        && !is_skipped
+                        && seen.insert(path.clone())
+                    {
+                        result.push(path);
+                    }
+                }
+            }
+
+            if !found {
+                self.warnings.push(Warning::FileNotFound {
+                    path: PathBuf::from(include.as_str()),
+                });
+            }
+        }
+
+        Ok(result)
+    }
+
+    fn glob_files(&mut self, pattern: &str, selection: Selection) -> Result<Vec<PathBuf>> {
         let current_dir = env::current_dir()?;
         let full_pattern = if Path::new(pattern).is_relative() {
             current_dir.join(pattern).to_string_lossy().into_owned()
@@ -33,39 +239,356 @@ impl PathResolver {
             source: e,
         })?;
 
+        self.load_selection(selection)?;
+        let selected = self.selected_files(selection);
         let mut result = Vec::new();
+        let mut unreadable = 0;
         for entry in paths {
             match entry {
                 Ok(path) => {
-                    if self.is_valid_file(&path) {
+                    // `..`を含むパターンの結果はそのままではgitのパスと比べられない
+                    let is_selected = selected.is_none_or(|selected| {
+                        fs::canonicalize(&path).is_ok_and(|path| selected.contains(&path))
+                    });
+                    if is_selected && self.is_valid_file(&path) && !self.is_ignored(&path) {
                         result.push(path);
                     }
                 }
-                Err(_) => {
-                    self.warnings.push(Warning::FileNotFound {
-                        path: PathBuf::from(pattern),
-                    });
+                Err(_) => unreadable += 1,
+            }
+        }
+        for _ in 0..unreadable {
+            self.warnings.push(Warning::FileNotFound {
+                path: PathBuf::from(pattern),
+            });
+        }
+
+        Ok(result)
+    }
+
+    /// Rustのクレートのルートファイルから`mod`宣言をたどり、モジュールツリーを構成するファイルを宣言順に返す
+    ///
+    /// `depth`を指定すると、ルートからその深さまでのモジュールに限る（`0`ならルートのみ）。
+    pub fn resolve_module_tree(
+        &mut self,
+        root_pattern: &str,
+        depth: Option<usize>,
+    ) -> Result<Vec<PathBuf>> {
+        let mut result = Vec::new();
+        for root in self.resolve_glob_terms(&[root_pattern.to_string()])? {
+            self.walk_module_file(&root, true, depth, &mut result);
+        }
+
+        let mut seen = HashSet::new();
+        result.retain(|path| seen.insert(path.clone()));
+        Ok(result)
+    }
+
+    fn walk_module_file(
+        &mut self,
+        path: &Path,
+        is_mod_rs: bool,
+        depth: Option<usize>,
+        result: &mut Vec<PathBuf>,
+    ) {
+        result.push(path.to_path_buf());
+        if depth == Some(0) {
+            return;
+        }
+
+        match SourceFile::parse(path) {
+            Ok(file) => {
+                let module = Module::from_file(&file, is_mod_rs);
+                self.walk_module(&module, path, depth.map(|depth| depth - 1), result);
+            }
+            Err(message) => self.warnings.push(Warning::InvalidRustSource { message }),
+        }
+    }
+
+    /// `depth`は子モジュールに残っている深さ
+    fn walk_module(
+        &mut self,
+        module: &Module,
+        declared_in: &Path,
+        depth: Option<usize>,
+        result: &mut Vec<PathBuf>,
+    ) {
+        for item_mod in module.mods() {
+            if let Some(inline) = module.inline(item_mod) {
+                if depth != Some(0) {
+                    self.walk_module(&inline, declared_in, depth.map(|depth| depth - 1), result);
+                }
+                continue;
+            }
+
+            match module.mod_file(item_mod) {
+                Ok((path, _)) if self.is_ignored(&path) => {}
+                Ok((path, is_mod_rs)) => self.walk_module_file(&path, is_mod_rs, depth, result),
+                Err(message) => self.warnings.push(Warning::ModuleNotFound {
+                    declared_in: declared_in.to_path_buf(),
+                    message,
+                }),
+            }
+        }
+    }
+
+    /// 起点のファイルと、そこからimportしているプロジェクト内のファイルを返す
+    ///
+    /// importをたどる順（幅優先）に並べる。`depth`を指定すると、起点からその段数までのimportに限る。
+    /// JS/TSとPythonのimportに対応する。
+    /// node_modulesのパッケージや標準ライブラリなど、プロジェクト外のモジュールは含めない。
+    pub fn resolve_dependencies(
+        &mut self,
+        entry_pattern: &str,
+        depth: Option<usize>,
+    ) -> Result<Vec<PathBuf>> {
+        let mut result = Vec::new();
+        let mut seen = HashSet::new();
+        let mut queue = VecDeque::new();
+
+        for entry in self.resolve_glob_terms(&[entry_pattern.to_string()])? {
+            if seen.insert(entry.clone()) {
+                queue.push_back((entry, 0));
+            }
+        }
+
+        while let Some((path, level)) = queue.pop_front() {
+            if depth.is_none_or(|depth| level < depth) {
+                for dependency in self.dependencies_of(&path) {
+                    if self.is_valid_file(&dependency)
+                        && !self.is_ignored(&dependency)
+                        && seen.insert(dependency.clone())
+                    {
+                        queue.push_back((dependency, level + 1));
+                    }
                 }
             }
+            result.push(path);
         }
 
+        Ok(result)
+    }
+
+    /// ファイルが直接importしているプロジェクト内のファイル
+    fn dependencies_of(&mut self, path: &Path) -> Vec<PathBuf> {
+        let Some(kind) = SourceKind::from_path(path) else {
+            return Vec::new();
+        };
+        let Ok(content) = fs::read_to_string(path) else {
+            return Vec::new();
+        };
+
+        let resolutions = match kind {
+            SourceKind::Script => {
+                let tsconfig = TsConfig::find_and_load(path);
+                dependency::script_imports(&content)
+                    .into_iter()
+                    .map(|specifier| {
+                        let resolution =
+                            dependency::resolve_script_import(path, &specifier, &tsconfig);
+                        (specifier, resolution)
+                    })
+                    .collect::<Vec<_>>()
+            }
+            SourceKind::Python => {
+                let roots = dependency::python_roots(path, &self.base_dir);
+                dependency::python_imports(&content)
+                    .into_iter()
+                    .flat_map(|import| {
+                        dependency::resolve_python_import(path, &import, &roots)
+                            .into_iter()
+                            .map(move |resolution| (import.module.clone(), resolution))
+                    })
+                    .collect::<Vec<_>>()
+            }
+        };
+
+        let mut result = Vec::new();
+        for (specifier, resolution) in resolutions {
+            match resolution {
+                Resolution::Local(dependency) => result.push(dependency),
+                Resolution::External => {}
+                Resolution::NotFound => self.warnings.push(Warning::DependencyNotFound {
+                    path: path.to_path_buf(),
+                    specifier,
+                }),
+            }
+        }
+        result
+    }
+
+    /// gitのリビジョン`base`（省略時はHEAD）から追加・変更されたファイルを返す
+    ///
+    /// 削除されたファイルは警告に名前を記録して除く。
+    pub fn resolve_changed(
+        &mut self,
+        base: Option<&str>,
+        include_untracked: bool,
+    ) -> Result<Vec<PathBuf>> {
+        let root = git::repository_root(&env::current_dir()?)?;
+        let files = git::changed_files(&root, base, include_untracked)?;
+
+        if !files.deleted.is_empty() {
+            self.warnings.push(Warning::DeletedFilesSkipped {
+                paths: files.deleted,
+            });
+        }
+
+        Ok(files
+            .changed
+            .into_iter()
+            .map(|path| root.join(path))
+            .filter(|path| self.is_valid_file(path) && !self.is_ignored(path))
+            .collect())
+    }
+
+    /// リビジョンを指定したパスにマッチするファイルを、カレントディレクトリからの相対パスで返す
+    ///
+    /// ファイルはワーキングツリーではなくそのリビジョンのものから探す。
+    pub fn resolve_revision(&mut self, path: &git::RevisionPath) -> Result<Vec<PathBuf>> {
+        let patterns = arguments::expand_braces(&path.pattern)
+            .iter()
+            .map(|expanded| {
+                Pattern::new(expanded.trim_start_matches("./")).map_err(|e| {
+                    Error::InvalidGlobPattern {
+                        pattern: path.pattern.clone(),
+                        source: e,
+                    }
+                })
+            })
+            .collect::<Result<Vec<_>>>()?;
+
+        let current_dir = env::current_dir()?;
+        let result = git::list_files(&current_dir, &path.revision)?
+            .into_iter()
+            .filter(|file| {
+                patterns
+                    .iter()
+                    .any(|pattern| pattern.matches_path_with(file, IGNORE_MATCH_OPTIONS))
+                    && !self.is_ignored(&current_dir.join(file))
+            })
+            .collect::<Vec<_>>();
+
         if result.is_empty() {
             self.warnings.push(Warning::FileNotFound {
-                path: PathBuf::from(pattern),
+                path: PathBuf::from(format!("{}:{}", path.revision, path.pattern)),
             });
         }
-
         Ok(result)
     }
 
-    pub fn resolve_regex(&mut self, pattern: &str) -> Result<Vec<PathBuf>> {
+    /// ディレクトリの中身をツリーとして返す（ディレクトリが先、それぞれ名前順）
+    ///
+    /// 除外パターンにマッチするものとバイナリファイルは含めない。
+    /// `.`で始まるファイル・ディレクトリは`include_hidden`を指定したときだけ含める。
+    /// `depth`を指定すると、その深さより下のディレクトリは展開しない（`1`なら直下のみ）。
+    pub fn resolve_tree(
+        &mut self,
+        root: &str,
+        depth: Option<usize>,
+        include_hidden: bool,
+    ) -> Result<Option<(PathBuf, Vec<TreeEntry>)>> {
+        let root_path = env::current_dir()?.join(root);
+        if !root_path.is_dir() {
+            self.warnings.push(Warning::FileNotFound {
+                path: PathBuf::from(root),
+            });
+            return Ok(None);
+        }
+
+        let entries = self.tree_entries(&root_path, depth, include_hidden)?;
+        Ok(Some((root_path, entries)))
+    }
+
+    fn tree_entries(
+        &self,
+        dir: &Path,
+        depth: Option<usize>,
+        include_hidden: bool,
+    ) -> io::Result<Vec<TreeEntry>> {
+        let mut dirs = Vec::new();
+        let mut files = Vec::new();
+        for entry in fs::read_dir(dir)? {
+            let path = entry?.path();
+            let is_hidden = path
+                .file_name()
+                .is_some_and(|name| name.to_string_lossy().starts_with('.'));
+            if (is_hidden && !include_hidden) || self.is_ignored(&path) {
+                continue;
+            }
+
+            if path.is_dir() {
+                dirs.push(path);
+            } else if self.is_valid_file(&path) {
+                files.push(path);
+            }
+        }
+        dirs.sort();
+        files.sort();
+
+        let child_depth = depth.map(|depth| depth.saturating_sub(1));
+        let mut entries = Vec::new();
+        for path in dirs {
+            let children = match child_depth {
+                Some(0) => Vec::new(),
+                _ => self.tree_entries(&path, child_depth, include_hidden)?,
+            };
+            entries.push(TreeEntry {
+                path,
+                children: Some(children),
+            });
+        }
+        entries.extend(files.into_iter().map(|path| TreeEntry {
+            path,
+            children: None,
+        }));
+        Ok(entries)
+    }
+
+    /// 正規表現にマッチするファイルを返す
+    ///
+    /// 末尾の`select=<mode>`でこの指示子だけの絞り込みを指定できる。
+    /// 絞り込む場合は、ディレクトリを走査せずgitの管理下のファイルから探す。
+    /// 生成されたファイルなどは`#ef`と同様に除外し、末尾の`include=<kinds>`で含められる。
+    pub fn resolve_regex(&mut self, argument: &str) -> Result<Vec<PathBuf>> {
+        let mut pattern = argument;
+        let mut selection = None;
+        let mut included = None;
+        while let Some((rest, option)) = pattern.rsplit_once(char::is_whitespace) {
+            if let (Some(value), None) = (option.strip_prefix(SELECT_OPTION), selection) {
+                selection = Some(parse_selection(argument, value)?);
+            } else if let (Some(value), None) = (option.strip_prefix(INCLUDE_OPTION), &included) {
+                included = Some(parse_included(argument, value)?);
+            } else {
+                break;
+            }
+            pattern = rest.trim_end();
+        }
+        let selection = selection.unwrap_or(self.selection);
+        let included = included.unwrap_or_default();
         let regex = Regex::new(pattern).map_err(|e| Error::InvalidRegexPattern {
             pattern: pattern.to_string(),
             source: e,
         })?;
 
+        self.load_selection(selection)?;
         let mut result = Vec::new();
-        self.walk_directory(&self.base_dir, &regex, &mut result)?;
+        match self.selected_files(selection) {
+            Some(selected) => {
+                for path in selected {
+                    let Ok(relative_path) = path.strip_prefix(&self.base_dir) else {
+                        continue;
+                    };
+                    let is_match = relative_path
+                        .to_str()
+                        .is_some_and(|path_str| regex.is_match(path_str));
+                    if is_match && !self.is_ignored(path) && self.is_valid_file(path) {
+                        result.push(path.clone());
+                    }
+                }
+            }
+            None => self.walk_directory(&self.base_dir, &regex, &mut result)?,
+        }
 
         if result.is_empty() {
             self.warnings.push(Warning::FileNotFound {
@@ -73,6 +596,8 @@ impl PathResolver {
             });
         }
 
+        let base_dir = self.base_dir.clone();
+        result.retain(|path| !self.skip_classified(path, &base_dir, &included));
         Ok(result)
     }
 
@@ -94,6 +619,10 @@ impl PathResolver {
                     path.clone()
                 };
 
+                if self.is_ignored(&path) {
+                    continue;
+                }
+
                 if path.is_dir() {
                     self.walk_directory(&path, regex, results)?;
                 } else if let Some(path_str) = relative_path.to_str() {
@@ -106,6 +635,61 @@ impl PathResolver {
         Ok(())
     }
 
+    /// 絞り込みの対象ファイルをgitから読み込んでおく（モードごとに1回だけ）
+    fn load_selection(&mut self, selection: Selection) -> Result<()> {
+        if selection == Selection::All || self.selected.contains_key(&selection) {
+            return Ok(());
+        }
+
+        let root = git::repository_root(&self.base_dir)?;
+        let root = root.canonicalize().unwrap_or(root);
+        let files = git::selected_files(&root, selection)?
+            .unwrap_or_default()
+            .into_iter()
+            .map(|path| root.join(path))
+            .collect();
+        self.selected.insert(selection, files);
+        Ok(())
+    }
+
+    /// 絞り込みの対象ファイルを返す（絞り込まない場合は`None`）
+    fn selected_files(&self, selection: Selection) -> Option<&BTreeSet<PathBuf>> {
+        self.selected.get(&selection)
+    }
+
+    /// 生成されたファイルなどで、`included`にない種類なら記録して`true`を返す
+    ///
+    /// ディレクトリ名による判定は`root`より下の部分にだけ適用する。
+    fn skip_classified(&mut self, path: &Path, root: &Path, included: &[FileClass]) -> bool {
+        let relative_path = path.strip_prefix(root).unwrap_or(path);
+        match self.classifier.classify(path, relative_path) {
+            Some(class) if !included.contains(&class) => {
+                let skipped = path.strip_prefix(&self.base_dir).unwrap_or(path);
+                self.skipped
+                    .entry(class)
+                    .or_default()
+                    .insert(skipped.to_path_buf());
+                true
+            }
+            _ => false,
+        }
+    }
+
+    fn is_ignored(&self, path: &Path) -> bool {
+        self.matches_any(&self.ignore_patterns, path)
+            || self
+                .ignore_rules
+                .as_ref()
+                .is_some_and(|rules| rules.is_ignored(path, path.is_dir()))
+    }
+
+    fn matches_any(&self, patterns: &[IgnorePattern], path: &Path) -> bool {
+        let relative_path = path.strip_prefix(&self.base_dir).unwrap_or(path);
+        patterns
+            .iter()
+            .any(|pattern| pattern.matches(relative_path))
+    }
+
     fn is_valid_file(&self, path: &Path) -> bool {
         if !path.is_file() {
             return false;
@@ -120,10 +704,59 @@ impl PathResolver {
     }
 
     pub fn take_warnings(&mut self) -> Warnings {
+        if !self.skipped.is_empty() {
+            let skipped = std::mem::take(&mut self.skipped)
+                .into_iter()
+                .map(|(class, paths)| (class, paths.into_iter().collect()))
+                .collect();
+            self.warnings
+                .push(Warning::ClassifiedFilesSkipped { skipped });
+        }
         std::mem::take(&mut self.warnings)
     }
 }
 
+fn parse_selection(argument: &str, value: &str) -> Result<Selection> {
+    clap::ValueEnum::from_str(value, false).map_err(|_| {
+        invalid_option(
+            argument,
+            "expected `select=` to be one of all, tracked-only, staged, modified, include-untracked",
+        )
+    })
+}
+
+fn parse_included(argument: &str, value: &str) -> Result<Vec<FileClass>> {
+    FileClass::parse_list(value).ok_or_else(|| {
+        invalid_option(
+            argument,
+            "expected `include=` to be a comma-separated list of generated, vendored, documentation or all",
+        )
+    })
+}
+
+/// ワイルドカードを含むglobパターンの、ワイルドカードより前の部分（含まなければ`None`）
+///
+/// ディレクトリ名による分類はこれより下の部分にだけ適用する。
+fn classify_root(pattern: &str) -> Result<Option<PathBuf>> {
+    let is_wildcard = |component: &str| component.contains(['*', '?', '[']);
+    if !is_wildcard(pattern) {
+        return Ok(None);
+    }
+
+    let literal = Path::new(pattern)
+        .components()
+        .take_while(|component| !is_wildcard(&component.as_os_str().to_string_lossy()))
+        .collect::<PathBuf>();
+    Ok(Some(env::current_dir()?.join(literal)))
+}
+
+fn invalid_option(argument: &str, message: &str) -> Error {
+    Error::InvalidDirectiveArgument {
+        argument: argument.to_string(),
+        message: message.to_string(),
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -169,6 +802,72 @@ mod tests {
         Ok(())
     }
 
+    #[test]
+    fn test_ignore_patterns() -> Result<()> {
+        let mut ctx = setup_test_files()?;
+        fs::create_dir_all("src/generated").unwrap();
+        fs::write("src/main.ts", "").unwrap();
+        fs::write("src/main.test.ts", "").unwrap();
+        fs::write("src/generated/api.ts", "").unwrap();
+
+        ctx.resolver
+            .add_ignore_patterns(&["*.test.ts", "src/generated"])?;
+
+        let paths = ctx.resolver.resolve_glob("src/**/*.ts")?;
+        assert_eq!(paths.len(), 1);
+        assert!(paths[0].ends_with("src/main.ts"));
+
+        let paths = ctx.resolver.resolve_regex(r"\.ts$")?;
+        assert_eq!(paths.len(), 1);
+        assert!(paths[0].ends_with("src/main.ts"));
+        Ok(())
+    }
+
+    #[test]
+    fn test_inline_exclude_patterns() -> Result<()> {
+        let mut ctx = setup_test_files()?;
+        fs::create_dir_all("src").unwrap();
+        fs::write("src/a.ts", "").unwrap();
+        fs::write("src/a.test.ts", "").unwrap();
+        fs::write("src/b.d.ts", "").unwrap();
+
+        let paths = ctx.resolver.resolve_glob("src/*.ts !*.test.ts !*.d.ts")?;
+        assert_eq!(paths.len(), 1);
+        assert!(paths[0].ends_with("src/a.ts"));
+
+        // インラインの除外パターンは他の指示子には影響しない
+        let paths = ctx.resolver.resolve_glob("src/*.ts")?;
+        assert_eq!(paths.len(), 3);
+        Ok(())
+    }
+
+    #[test]
+    fn test_multiple_patterns() -> Result<()> {
+        let mut ctx = setup_test_files()?;
+        fs::create_dir_all("src/api").unwrap();
+        fs::create_dir_all("src/db").unwrap();
+        fs::create_dir_all("src/ui").unwrap();
+        fs::write("src/api/a.rs", "").unwrap();
+        fs::write("src/db/b.rs", "").unwrap();
+        fs::write("src/ui/c.rs", "").unwrap();
+        fs::write("my notes.txt", "").unwrap();
+
+        let paths = ctx
+            .resolver
+            .resolve_glob(r#"test2.txt src/{api,db}/*.rs "my notes.txt" *.txt"#)?;
+        let names = paths
+            .iter()
+            .map(|path| path.file_name().unwrap().to_string_lossy().into_owned())
+            .collect::<Vec<_>>();
+
+        // 記述順に並び、重複（*.txtに含まれるtest2.txt, my notes.txt）は除かれる
+        assert_eq!(
+            names,
+            vec!["test2.txt", "a.rs", "b.rs", "my notes.txt", "test1.txt"]
+        );
+        Ok(())
+    }
+
     #[test]
     fn test_invalid_glob_pattern() -> Result<()> {
         let mut ctx = setup_test_files()?;
@@ -184,4 +883,243 @@ mod tests {
         assert!(result.is_err());
         Ok(())
     }
+
+    #[test]
+    fn test_resolve_tree() -> Result<()> {
+        let mut context = setup_test_files()?;
+        fs::create_dir_all("src/nested/deep").unwrap();
+        fs::create_dir_all(".git").unwrap();
+        fs::write("src/main.rs", "fn main() {}").unwrap();
+        fs::write("src/nested/deep/a.rs", "").unwrap();
+        fs::write("src/image.bin", [0u8, 1, 2]).unwrap();
+        fs::write("src/main.test.rs", "").unwrap();
+        fs::write(".env", "").unwrap();
+        context.resolver.add_ignore_patterns(&["*.test.rs"])?;
+
+        let (root, entries) = context.resolver.resolve_tree(".", Some(2), false)?.unwrap();
+        let names = |entries: &[TreeEntry]| {
+            entries
+                .iter()
+                .map(|entry| entry.path.strip_prefix(&root).unwrap().to_path_buf())
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(
+            names(&entries),
+            vec![
+                PathBuf::from("src"),
+                PathBuf::from("test1.txt"),
+                PathBuf::from("test2.txt"),
+            ]
+        );
+        let src = entries[0].children.as_ref().unwrap();
+        assert_eq!(
+            names(src),
+            vec![PathBuf::from("src/nested"), PathBuf::from("src/main.rs")]
+        );
+        // depth=2なのでsrc/nestedの中は展開しない
+        assert_eq!(src[0].children, Some(Vec::new()));
+
+        let (_, entries) = context.resolver.resolve_tree(".", None, true)?.unwrap();
+        assert_eq!(
+            names(&entries),
+            vec![
+                PathBuf::from(".git"),
+                PathBuf::from("src"),
+                PathBuf::from(".env"),
+                PathBuf::from("test1.txt"),
+                PathBuf::from("test2.txt"),
+            ]
+        );
+
+        assert!(context
+            .resolver
+            .resolve_tree("missing", None, false)?
+            .is_none());
+        Ok(())
+    }
+
+    #[test]
+    fn test_ignore_files() -> Result<()> {
+        let mut context = setup_test_files()?;
+        fs::create_dir_all(".git").unwrap();
+        fs::create_dir_all("target/debug").unwrap();
+        fs::write(".gitignore", "target/\n").unwrap();
+        fs::write(".efignore", "test2.txt\n").unwrap();
+        fs::write("target/debug/out.txt", "build output").unwrap();
+
+        let mut paths = context.resolver.resolve_regex(r"\.txt$")?;
+        paths.sort();
+        assert_eq!(paths.len(), 3);
+
+        context.resolver.use_ignore_files(env::current_dir()?)?;
+        let paths = context.resolver.resolve_regex(r"\.txt$")?;
+        let names = paths
+            .iter()
+            .map(|path| path.file_name().unwrap().to_str().unwrap())
+            .collect::<Vec<_>>();
+        assert_eq!(names, vec!["test1.txt"]);
+        assert!(context
+            .resolver
+            .resolve_glob("**/*.txt")?
+            .iter()
+            .all(|path| path.ends_with("test1.txt")));
+        Ok(())
+    }
+
+    #[test]
+    fn test_classified_files() -> Result<()> {
+        let mut context = setup_test_files()?;
+        fs::create_dir_all("vendor/lib").unwrap();
+        fs::create_dir_all("docs").unwrap();
+        fs::write("package-lock.json", "{}").unwrap();
+        fs::write("app.min.js", "").unwrap();
+        fs::write("vendor/lib/lib.js", "").unwrap();
+        fs::write("docs/guide.txt", "").unwrap();
+        fs::write("main.js", "").unwrap();
+
+        let paths = context.resolver.resolve_glob("**/*.js")?;
+        assert_eq!(paths.len(), 1);
+        assert!(paths[0].ends_with("main.js"));
+
+        // 明示したディレクトリの中や、ワイルドカードを含まないパスは除外しない
+        assert_eq!(context.resolver.resolve_glob("vendor/**/*.js")?.len(), 1);
+        assert_eq!(context.resolver.resolve_glob("package-lock.json")?.len(), 1);
+
+        let paths = context
+            .resolver
+            .resolve_glob("**/*.js include=generated,vendored")?;
+        assert_eq!(paths.len(), 3);
+        let paths = context.resolver.resolve_regex(r"\.txt$ include=all")?;
+        assert_eq!(paths.len(), 3);
+        let paths = context.resolver.resolve_regex(r"\.txt$")?;
+        assert_eq!(paths.len(), 2);
+        assert!(context.resolver.resolve_glob("*.js include=tests").is_err());
+
+        let skipped = context
+            .resolver
+            .take_warnings()
+            .into_iter()
+            .find_map(|warning| match warning {
+                Warning::ClassifiedFilesSkipped { skipped } => Some(skipped),
+                _ => None,
+            })
+            .unwrap();
+        assert_eq!(
+            skipped[&FileClass::Generated],
+            vec![PathBuf::from("app.min.js")]
+        );
+        assert_eq!(
+            skipped[&FileClass::Vendored],
+            vec![PathBuf::from("vendor/lib/lib.js")]
+        );
+        assert_eq!(
+            skipped[&FileClass::Documentation],
+            vec![PathBuf::from("docs/guide.txt")]
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_resolve_module_tree() -> Result<()> {
+        let mut ctx = setup_test_files()?;
+        let files = [
+            (
+                "src/lib.rs",
+                "mod b;\nmod a;\nmod inline {\n    mod nested;\n    #[path = \"other.rs\"]\n    mod renamed;\n}\nmod missing;\n",
+            ),
+            ("src/a.rs", "#[path = \"custom.rs\"]\nmod child;\n"),
+            ("src/custom.rs", ""),
+            ("src/b/mod.rs", "mod c;\n"),
+            ("src/b/c.rs", ""),
+            ("src/inline/nested.rs", ""),
+            ("src/inline/other.rs", ""),
+            ("src/unused.rs", ""),
+        ];
+        for (path, content) in files {
+            fs::create_dir_all(Path::new(path).parent().unwrap()).unwrap();
+            fs::write(path, content).unwrap();
+        }
+
+        let relative = |paths: Vec<PathBuf>| {
+            let current_dir = env::current_dir().unwrap();
+            paths
+                .iter()
+                .map(|path| path.strip_prefix(&current_dir).unwrap().to_path_buf())
+                .collect::<Vec<_>>()
+        };
+
+        let paths = ctx.resolver.resolve_module_tree("src/lib.rs", None)?;
+        assert_eq!(
+            relative(paths),
+            [
+                "src/lib.rs",
+                "src/b/mod.rs",
+                "src/b/c.rs",
+                "src/a.rs",
+                "src/custom.rs",
+                "src/inline/nested.rs",
+                "src/inline/other.rs",
+            ]
+            .map(PathBuf::from)
+        );
+        assert!(ctx
+            .resolver
+            .take_warnings()
+            .into_iter()
+            .any(|warning| matches!(warning, Warning::ModuleNotFound { .. })));
+
+        let paths = ctx.resolver.resolve_module_tree("src/lib.rs", Some(1))?;
+        assert_eq!(
+            relative(paths),
+            ["src/lib.rs", "src/b/mod.rs", "src/a.rs"].map(PathBuf::from)
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_resolve_dependencies() -> Result<()> {
+        let mut ctx = setup_test_files()?;
+        let files = [
+            (
+                "src/TaskList.tsx",
+                "import React from \"react\";\nimport { TaskItem } from \"./TaskItem\";\nimport { useTasks } from \"./hooks\";\n",
+            ),
+            ("src/TaskItem.tsx", "import { format } from \"./format\";\n"),
+            ("src/hooks/index.ts", "import \"./missing\";\n"),
+            ("src/format.ts", ""),
+        ];
+        for (path, content) in files {
+            fs::create_dir_all(Path::new(path).parent().unwrap()).unwrap();
+            fs::write(path, content).unwrap();
+        }
+
+        let file_names = |paths: Vec<PathBuf>| {
+            paths
+                .iter()
+                .map(|path| path.file_name().unwrap().to_string_lossy().into_owned())
+                .collect::<Vec<_>>()
+        };
+
+        let paths = ctx
+            .resolver
+            .resolve_dependencies("src/TaskList.tsx", None)?;
+        assert_eq!(
+            file_names(paths),
+            ["TaskList.tsx", "TaskItem.tsx", "index.ts", "format.ts"]
+        );
+        assert!(ctx
+            .resolver
+            .take_warnings()
+            .into_iter()
+            .any(|warning| matches!(warning, Warning::DependencyNotFound { .. })));
+
+        let paths = ctx
+            .resolver
+            .resolve_dependencies("src/TaskList.tsx", Some(1))?;
+        assert_eq!(
+            file_names(paths),
+            ["TaskList.tsx", "TaskItem.tsx", "index.ts"]
+        );
+        Ok(())
+    }
 }
@@ -0,0 +1,490 @@
+use crate::eftemplate;
+use crate::extract::Extracted;
+use std::fs;
+use std::path::{Path, PathBuf};
+use syn::punctuated::Punctuated;
+use syn::spanned::Spanned;
+
+/// クレートのルートファイルとして探すファイル（優先順）
+const CRATE_ROOT_FILES: &[&str] = &["src/lib.rs", "src/main.rs"];
+
+/// パース済みのRustのソースファイル
+pub struct SourceFile {
+    pub path: PathBuf,
+    pub content: String,
+    pub syntax: syn::File,
+}
+
+impl SourceFile {
+    pub fn parse<P: AsRef<Path>>(path: P) -> Result<Self, String> {
+        let path = path.as_ref();
+        let content = fs::read_to_string(path)
+            .map_err(|e| format!("failed to read {}: {}", path.display(), e))?;
+        let syntax = syn::parse_file(&content)
+            .map_err(|e| format!("failed to parse {}: {}", path.display(), e))?;
+
+        Ok(Self {
+            path: path.to_path_buf(),
+            content,
+            syntax,
+        })
+    }
+}
+
+/// モジュールの中身（ファイル全体、またはインラインモジュールの本体）と、子モジュールのファイルを探すディレクトリ
+pub struct Module<'a> {
+    pub items: &'a [syn::Item],
+    pub dir: PathBuf,
+    /// `#[path = "..."]`の起点（ファイルならそのファイルのディレクトリ、インラインモジュールなら`dir`）
+    path_dir: PathBuf,
+}
+
+impl<'a> Module<'a> {
+    /// ファイルをモジュールとして開く
+    ///
+    /// `is_mod_rs`が`true`のファイル（クレートのルート、`mod.rs`、`#[path]`で指定したファイル）は、
+    /// 子モジュールを同じディレクトリから探す。それ以外の`foo.rs`は`foo/`から探す。
+    pub fn from_file(file: &'a SourceFile, is_mod_rs: bool) -> Self {
+        let parent = file.path.parent().unwrap_or_else(|| Path::new("."));
+        let dir = if is_mod_rs || file.path.file_name().is_some_and(|name| name == "mod.rs") {
+            parent.to_path_buf()
+        } else {
+            parent.join(file.path.file_stem().unwrap_or_default())
+        };
+
+        Self {
+            items: &file.syntax.items,
+            dir,
+            path_dir: parent.to_path_buf(),
+        }
+    }
+
+    /// 名前が一致する`mod`宣言を探す
+    pub fn find_mod(&self, name: &str) -> Option<&'a syn::ItemMod> {
+        self.items.iter().find_map(|item| match item {
+            syn::Item::Mod(item_mod) if item_mod.ident == name => Some(item_mod),
+            _ => None,
+        })
+    }
+
+    /// `mod`宣言（宣言順）
+    pub fn mods(&self) -> impl Iterator<Item = &'a syn::ItemMod> {
+        self.items.iter().filter_map(|item| match item {
+            syn::Item::Mod(item_mod) => Some(item_mod),
+            _ => None,
+        })
+    }
+
+    /// インラインモジュールの中身
+    pub fn inline(&self, item_mod: &'a syn::ItemMod) -> Option<Module<'a>> {
+        let (_, items) = item_mod.content.as_ref()?;
+        let dir = self
+            .dir
+            .join(path_attribute(item_mod).unwrap_or_else(|| item_mod.ident.to_string()));
+        Some(Module {
+            items,
+            path_dir: dir.clone(),
+            dir,
+        })
+    }
+
+    /// `mod name;`宣言に対応するファイルのパスと、それが`mod.rs`相当かどうかを返す
+    ///
+    /// `#[path = "..."]`があればそれに従い、なければ`name.rs`、`name/mod.rs`の順に探す。
+    /// `#[path]`は宣言したファイルのディレクトリ（インラインモジュールの中ではそのモジュールのディレクトリ）を起点にする。
+    pub fn mod_file(&self, item_mod: &syn::ItemMod) -> Result<(PathBuf, bool), String> {
+        if let Some(path) = path_attribute(item_mod) {
+            let path = self.path_dir.join(path);
+            return if path.is_file() {
+                Ok((path, true))
+            } else {
+                Err(format!("module file {} not found", path.display()))
+            };
+        }
+
+        let name = item_mod.ident.to_string();
+        let candidates = [
+            (self.dir.join(format!("{}.rs", name)), false),
+            (self.dir.join(&name).join("mod.rs"), true),
+        ];
+        candidates
+            .iter()
+            .find(|(path, _)| path.is_file())
+            .cloned()
+            .ok_or_else(|| {
+                format!(
+                    "module `{}` not found (expected {} or {})",
+                    name,
+                    candidates[0].0.display(),
+                    candidates[1].0.display()
+                )
+            })
+    }
+}
+
+/// `#[path = "..."]`属性の値
+fn path_attribute(item_mod: &syn::ItemMod) -> Option<String> {
+    item_mod.attrs.iter().find_map(|attr| {
+        let syn::Meta::NameValue(meta) = &attr.meta else {
+            return None;
+        };
+        if !meta.path.is_ident("path") {
+            return None;
+        }
+        match &meta.value {
+            syn::Expr::Lit(syn::ExprLit {
+                lit: syn::Lit::Str(value),
+                ..
+            }) => Some(value.value()),
+            _ => None,
+        }
+    })
+}
+
+/// テスト用の要素（`#[cfg(test)]`、`#[cfg(all(test, ...))]`、`#[cfg(any(test, ...))]`）かどうか
+pub fn is_cfg_test(attrs: &[syn::Attribute]) -> bool {
+    attrs.iter().any(|attr| {
+        attr.path().is_ident("cfg") && attr.parse_args().is_ok_and(|meta| mentions_test(&meta))
+    })
+}
+
+/// cfgの条件に`test`が含まれるか（`not(test)`は除く）
+fn mentions_test(meta: &syn::Meta) -> bool {
+    match meta {
+        syn::Meta::Path(path) => path.is_ident("test"),
+        syn::Meta::List(list) if list.path.is_ident("all") || list.path.is_ident("any") => list
+            .parse_args_with(Punctuated::<syn::Meta, syn::Token![,]>::parse_terminated)
+            .is_ok_and(|metas| metas.iter().any(mentions_test)),
+        _ => false,
+    }
+}
+
+/// 要素が占める行の範囲（1始まり、両端を含む）。属性とドキュメントコメントを含む
+fn line_span<T: Spanned>(item: &T) -> (usize, usize) {
+    let span = item.span();
+    (span.start().line, span.end().line)
+}
+
+/// カレントディレクトリから上位に向かってCargo.tomlを探し、クレートのルートファイルを返す
+pub fn find_crate_root<P: AsRef<Path>>(start_dir: P) -> Option<PathBuf> {
+    let manifest = eftemplate::find_upwards(start_dir.as_ref(), "Cargo.toml").ok()??;
+    let package_dir = manifest.parent()?;
+
+    CRATE_ROOT_FILES
+        .iter()
+        .map(|file| package_dir.join(file))
+        .find(|path| path.is_file())
+}
+
+/// `crate::path_resolver::PathResolver`のようなアイテムのパスから、定義を含むファイルと定義部分を取り出す
+///
+/// 型や関数などのアイテムの場合は、定義と同じモジュールにある`impl`ブロックもまとめて返す。
+/// モジュールの場合はファイル（インラインモジュールの場合はその部分）全体を返し、
+/// `include_tests`が`false`なら`#[cfg(test)]`の要素（アイテムの場合は`impl`ブロック）を取り除く。
+pub fn find_item(
+    crate_root: &Path,
+    item_path: &[&str],
+    include_tests: bool,
+) -> Result<(SourceFile, Extracted), String> {
+    let mut file = SourceFile::parse(crate_root)?;
+    let mut inline_path = Vec::new();
+    let mut is_mod_rs = true;
+
+    // ファイルをまたぐ`mod`宣言をたどる。インラインモジュールは同じファイル内の名前として記録する
+    for (i, name) in item_path.iter().enumerate() {
+        let module = descend_inline(&file, is_mod_rs, &inline_path)?;
+        let Some(item_mod) = module.find_mod(name) else {
+            let rest = &item_path[i..];
+            return if rest.len() == 1 {
+                let extracted = extract_item(&file, &module, name, include_tests)
+                    .ok_or_else(|| format!("`{}` not found in {}", name, file.path.display()))?;
+                Ok((file, extracted))
+            } else {
+                Err(format!(
+                    "module `{}` not found in {}",
+                    name,
+                    file.path.display()
+                ))
+            };
+        };
+
+        if item_mod.content.is_some() {
+            inline_path.push(name.to_string());
+            continue;
+        }
+
+        let (path, mod_rs) = module.mod_file(item_mod)?;
+        file = SourceFile::parse(path)?;
+        inline_path.clear();
+        is_mod_rs = mod_rs;
+    }
+
+    let extracted = extract_module(&file, is_mod_rs, &inline_path, include_tests)?;
+    Ok((file, extracted))
+}
+
+/// ファイル内のインラインモジュールをたどる
+fn descend_inline<'a>(
+    file: &'a SourceFile,
+    is_mod_rs: bool,
+    inline_path: &[String],
+) -> Result<Module<'a>, String> {
+    let mut module = Module::from_file(file, is_mod_rs);
+    for name in inline_path {
+        let item_mod = module
+            .find_mod(name)
+            .ok_or_else(|| format!("module `{}` not found in {}", name, file.path.display()))?;
+        module = module
+            .inline(item_mod)
+            .ok_or_else(|| format!("module `{}` is not inline", name))?;
+    }
+    Ok(module)
+}
+
+/// アイテムの定義と、その型に対する`impl`ブロックを取り出す
+///
+/// `include_tests`が`false`なら`#[cfg(test)]`の`impl`ブロックは含めない。
+fn extract_item(
+    file: &SourceFile,
+    module: &Module,
+    name: &str,
+    include_tests: bool,
+) -> Option<Extracted> {
+    let ranges = module
+        .items
+        .iter()
+        .filter(|item| {
+            item_name(item).is_some_and(|ident| ident == name)
+                || (impl_of(item, name) && (include_tests || !is_cfg_test(item_attrs(item))))
+        })
+        .map(line_span)
+        .collect::<Vec<_>>();
+
+    if ranges.is_empty() {
+        return None;
+    }
+
+    let lines = file.content.lines().collect::<Vec<_>>();
+    let content = ranges
+        .iter()
+        .map(|&(start, end)| lines[start - 1..end.min(lines.len())].join("\n"))
+        .collect::<Vec<_>>()
+        .join("\n\n");
+
+    Some(Extracted {
+        content,
+        start_line: ranges.first()?.0,
+        end_line: ranges.last()?.1,
+    })
+}
+
+/// モジュール全体を取り出す
+fn extract_module(
+    file: &SourceFile,
+    is_mod_rs: bool,
+    inline_path: &[String],
+    include_tests: bool,
+) -> Result<Extracted, String> {
+    let lines = file.content.lines().collect::<Vec<_>>();
+
+    // インラインモジュールの場合は`mod name { ... }`全体
+    let (start, end) = match inline_path.split_last() {
+        Some((name, parents)) => {
+            let parent = descend_inline(file, is_mod_rs, parents)?;
+            let item_mod = parent
+                .find_mod(name)
+                .ok_or_else(|| format!("module `{}` not found", name))?;
+            line_span(item_mod)
+        }
+        None => (1, lines.len()),
+    };
+
+    let module = descend_inline(file, is_mod_rs, inline_path)?;
+    let removed = if include_tests {
+        Vec::new()
+    } else {
+        module
+            .items
+            .iter()
+            .filter(|item| is_cfg_test(item_attrs(item)))
+            .map(line_span)
+            .collect::<Vec<_>>()
+    };
+
+    let kept = (start..=end)
+        .filter(|line| !removed.iter().any(|&(s, e)| (s..=e).contains(line)))
+        .filter_map(|line| lines.get(line - 1).copied())
+        .collect::<Vec<_>>();
+
+    let content = kept.join("\n").trim_end().to_string();
+    Ok(Extracted {
+        content,
+        start_line: start,
+        end_line: end,
+    })
+}
+
+fn item_name(item: &syn::Item) -> Option<&syn::Ident> {
+    match item {
+        syn::Item::Const(item) => Some(&item.ident),
+        syn::Item::Enum(item) => Some(&item.ident),
+        syn::Item::Fn(item) => Some(&item.sig.ident),
+        syn::Item::Macro(item) => item.ident.as_ref(),
+        syn::Item::Static(item) => Some(&item.ident),
+        syn::Item::Struct(item) => Some(&item.ident),
+        syn::Item::Trait(item) => Some(&item.ident),
+        syn::Item::TraitAlias(item) => Some(&item.ident),
+        syn::Item::Type(item) => Some(&item.ident),
+        syn::Item::Union(item) => Some(&item.ident),
+        _ => None,
+    }
+}
+
+/// `impl Name`または`impl Trait for Name`かどうか
+fn impl_of(item: &syn::Item, name: &str) -> bool {
+    let syn::Item::Impl(item_impl) = item else {
+        return false;
+    };
+    let syn::Type::Path(type_path) = item_impl.self_ty.as_ref() else {
+        return false;
+    };
+    type_path
+        .path
+        .segments
+        .last()
+        .is_some_and(|segment| segment.ident == name)
+}
+
+pub fn item_attrs(item: &syn::Item) -> &[syn::Attribute] {
+    match item {
+        syn::Item::Const(item) => &item.attrs,
+        syn::Item::Enum(item) => &item.attrs,
+        syn::Item::ExternCrate(item) => &item.attrs,
+        syn::Item::Fn(item) => &item.attrs,
+        syn::Item::ForeignMod(item) => &item.attrs,
+        syn::Item::Impl(item) => &item.attrs,
+        syn::Item::Macro(item) => &item.attrs,
+        syn::Item::Mod(item) => &item.attrs,
+        syn::Item::Static(item) => &item.attrs,
+        syn::Item::Struct(item) => &item.attrs,
+        syn::Item::Trait(item) => &item.attrs,
+        syn::Item::TraitAlias(item) => &item.attrs,
+        syn::Item::Type(item) => &item.attrs,
+        syn::Item::Union(item) => &item.attrs,
+        syn::Item::Use(item) => &item.attrs,
+        _ => &[],
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+    use tempfile::TempDir;
+
+    fn setup() -> TempDir {
+        let dir = TempDir::new().unwrap();
+        let files = [
+            ("Cargo.toml", "[package]\nname = \"sample\"\n"),
+            (
+                "src/lib.rs",
+                "pub mod resolver;\n#[path = \"other/util.rs\"]\nmod util;\nmod inline {\n    pub mod nested;\n}\npub mod outer;\n",
+            ),
+            (
+                "src/outer.rs",
+                "#[path = \"sibling.rs\"]\npub mod sibling;\nmod inner {\n    #[path = \"deep.rs\"]\n    pub mod deep;\n}\n",
+            ),
+            ("src/sibling.rs", "pub struct Sibling;\n"),
+            ("src/outer/inner/deep.rs", "pub struct Deep;\n"),
+            (
+                "src/resolver.rs",
+                "use std::path::PathBuf;\n\n/// Resolves paths\n#[derive(Debug)]\npub struct Resolver {\n    base: PathBuf,\n}\n\nfn helper() {}\n\nimpl Resolver {\n    pub fn new() -> Self {\n        todo!()\n    }\n}\n\nimpl Default for Resolver {\n    fn default() -> Self {\n        Self::new()\n    }\n}\n\n#[cfg(test)]\nimpl Resolver {\n    fn fixture() -> Self {\n        todo!()\n    }\n}\n\n#[cfg(test)]\nmod tests {\n    #[test]\n    fn test() {}\n}\n\n#[cfg(all(test, feature = \"slow\"))]\nmod slow_tests {}\n\n#[cfg(any(test, feature = \"testing\"))]\nmod test_support {}\n\n#[cfg(not(test))]\nmod runtime {}\n",
+            ),
+            ("src/other/util.rs", "pub fn util() {}\n"),
+            ("src/inline/nested.rs", "pub struct Nested;\n"),
+        ];
+        for (path, content) in files {
+            let path = dir.path().join(path);
+            fs::create_dir_all(path.parent().unwrap()).unwrap();
+            fs::write(path, content).unwrap();
+        }
+        dir
+    }
+
+    #[test]
+    fn test_find_crate_root() {
+        let dir = setup();
+        assert_eq!(
+            find_crate_root(dir.path().join("src/other")),
+            Some(dir.path().join("src/lib.rs"))
+        );
+    }
+
+    #[test]
+    fn test_struct_with_impl_blocks() {
+        let dir = setup();
+        let root = dir.path().join("src/lib.rs");
+
+        let (file, extracted) = find_item(&root, &["resolver", "Resolver"], true).unwrap();
+        assert_eq!(file.path, dir.path().join("src/resolver.rs"));
+        assert!(extracted
+            .content
+            .starts_with("/// Resolves paths\n#[derive(Debug)]\npub struct Resolver {"));
+        assert!(extracted.content.contains("impl Resolver {"));
+        assert!(extracted.content.contains("impl Default for Resolver {"));
+        assert!(!extracted.content.contains("fn helper"));
+        assert!(extracted.content.contains("fn fixture"));
+        assert_eq!((extracted.start_line, extracted.end_line), (3, 28));
+
+        let (_, extracted) = find_item(&root, &["resolver", "Resolver"], false).unwrap();
+        assert!(!extracted.content.contains("fn fixture"));
+        assert_eq!((extracted.start_line, extracted.end_line), (3, 21));
+    }
+
+    #[test]
+    fn test_module_without_tests() {
+        let dir = setup();
+        let root = dir.path().join("src/lib.rs");
+
+        let (_, extracted) = find_item(&root, &["resolver"], true).unwrap();
+        assert!(extracted.content.contains("mod tests"));
+
+        let (_, extracted) = find_item(&root, &["resolver"], false).unwrap();
+        assert!(!extracted.content.contains("mod tests"));
+        assert!(!extracted.content.contains("fn fixture"));
+        assert!(!extracted.content.contains("mod slow_tests"));
+        assert!(!extracted.content.contains("mod test_support"));
+        assert!(extracted
+            .content
+            .ends_with("#[cfg(not(test))]\nmod runtime {}"));
+    }
+
+    #[test]
+    fn test_path_attribute_and_inline_modules() {
+        let dir = setup();
+        let root = dir.path().join("src/lib.rs");
+
+        let (file, extracted) = find_item(&root, &["util", "util"], true).unwrap();
+        assert_eq!(file.path, dir.path().join("src/other/util.rs"));
+        assert_eq!(extracted.content, "pub fn util() {}");
+
+        let (file, extracted) = find_item(&root, &["inline", "nested", "Nested"], true).unwrap();
+        assert_eq!(file.path, dir.path().join("src/inline/nested.rs"));
+        assert_eq!(extracted.content, "pub struct Nested;");
+
+        // `src/outer.rs`の`#[path]`は`src/`から、その中のインラインモジュールでは`src/outer/inner/`から解決する
+        let (file, _) = find_item(&root, &["outer", "sibling", "Sibling"], true).unwrap();
+        assert_eq!(file.path, dir.path().join("src/sibling.rs"));
+        let (file, _) = find_item(&root, &["outer", "inner", "deep", "Deep"], true).unwrap();
+        assert_eq!(file.path, dir.path().join("src/outer/inner/deep.rs"));
+    }
+
+    #[test]
+    fn test_not_found() {
+        let dir = setup();
+        let root = dir.path().join("src/lib.rs");
+
+        assert!(find_item(&root, &["resolver", "Missing"], true).is_err());
+        assert!(find_item(&root, &["missing", "Item"], true).is_err());
+    }
+}
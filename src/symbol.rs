@@ -0,0 +1,495 @@
+use crate::extract::Extracted;
+use regex::Regex;
+use std::ops::Range;
+
+/// シンボルの抽出に失敗した理由
+#[derive(Debug, PartialEq)]
+pub enum SymbolError {
+    /// 対応していない言語
+    UnsupportedLanguage,
+    /// シンボルが見つからない
+    NotFound,
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum Syntax {
+    Rust,
+    /// TypeScript / JavaScript
+    Script,
+    Python,
+}
+
+impl Syntax {
+    fn from_language(language: &str) -> Option<Self> {
+        match language {
+            "rust" => Some(Self::Rust),
+            "typescript" | "tsx" | "javascript" | "jsx" => Some(Self::Script),
+            "python" => Some(Self::Python),
+            _ => None,
+        }
+    }
+
+    /// 定義の先頭行にマッチする正規表現
+    ///
+    /// `nested`が`true`の場合は、クラスのメソッドのような入れ子の定義も対象にする。
+    fn definition_patterns(self, name: &str, nested: bool) -> Vec<Regex> {
+        let name = regex::escape(name);
+        let mut patterns = match self {
+            Self::Rust => vec![
+                format!(
+                    r#"^\s*(?:pub(?:\([^)]*\))?\s+)?(?:(?:const|async|unsafe|default|extern\s+"[^"]*")\s+)*(?:fn|struct|enum|trait|union|type|mod|static|const)\s+{}\b"#,
+                    name
+                ),
+                format!(r"^\s*macro_rules!\s*{}\b", name),
+            ],
+            Self::Script => vec![
+                format!(
+                    r"^\s*(?:export\s+)?(?:default\s+)?(?:declare\s+)?(?:abstract\s+)?(?:async\s+)?(?:function\s*\*?|class|interface|enum|type|namespace|module)\s+{}\b",
+                    name
+                ),
+                format!(
+                    r"^\s*(?:export\s+)?(?:declare\s+)?(?:const|let|var)\s+{}\b",
+                    name
+                ),
+            ],
+            Self::Python => vec![format!(r"^\s*(?:async\s+)?(?:def|class)\s+{}\b", name)],
+        };
+
+        if nested && self == Self::Script {
+            patterns.push(format!(
+                r"^\s*(?:(?:public|private|protected|static|readonly|async|abstract|override|get|set)\s+)*\*?{}\s*(?:<[^>]*>)?\s*\(",
+                name
+            ));
+        }
+
+        patterns
+            .iter()
+            .map(|pattern| Regex::new(pattern).unwrap())
+            .collect()
+    }
+
+    /// Rustの`impl`ブロックの先頭行にマッチする正規表現
+    ///
+    /// `trait`に実装するトレイト（inherent implなら無し）、`name`に実装先の型名を取り出す。
+    fn impl_pattern(self) -> Option<Regex> {
+        (self == Self::Rust).then(|| {
+            Regex::new(
+                r"^\s*(?:unsafe\s+)?impl\b(?:<[^{]*?>)?\s+(?:(?P<trait>[\w:]+(?:<[^{]*?>)?)\s+for\s+)?(?:\w+::)*(?P<name>\w+)\b",
+            )
+            .unwrap()
+        })
+    }
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum Kind {
+    /// 関数・型・クラスなどの定義
+    Item,
+    /// Rustの`impl Type`ブロック
+    InherentImpl,
+    /// Rustの`impl Trait for Type`ブロック
+    TraitImpl,
+}
+
+/// ファイル内容から、名前を指定した関数・型・クラスなどの定義を取り出す
+///
+/// `Config::new`のように`::`で区切ると、impl/クラスの中の定義を探す。
+/// Rustの型には、そのinherent implブロック（`impl Config { ... }`）も続けて含める。
+/// 定義の直前にあるドキュメントコメント・属性・デコレーターも含める。
+pub fn extract(content: &str, language: &str, symbol: &str) -> Result<Extracted, SymbolError> {
+    let syntax = Syntax::from_language(language).ok_or(SymbolError::UnsupportedLanguage)?;
+    let lines = content.lines().collect::<Vec<_>>();
+    let names = symbol.split("::").collect::<Vec<_>>();
+
+    let ranges = find_nested(&lines, syntax, 0..lines.len(), &names, false)
+        .ok_or(SymbolError::NotFound)?
+        .into_iter()
+        .map(|range| leading_comments_start(&lines, syntax, range.start)..range.end)
+        .collect::<Vec<_>>();
+
+    Ok(Extracted {
+        content: ranges
+            .iter()
+            .map(|range| lines[range.clone()].join("\n"))
+            .collect::<Vec<_>>()
+            .join("\n\n"),
+        start_line: ranges[0].start + 1,
+        end_line: ranges[ranges.len() - 1].end,
+    })
+}
+
+/// `names`を外側から順にたどり、最も内側の定義（とそのinherent implブロック）の行範囲を返す
+///
+/// 定義が見つからない場合は、implブロックだけを返す。
+fn find_nested(
+    lines: &[&str],
+    syntax: Syntax,
+    range: Range<usize>,
+    names: &[&str],
+    nested: bool,
+) -> Option<Vec<Range<usize>>> {
+    let (name, rest) = names.split_first()?;
+    let definitions = find_definitions(lines, syntax, range, name, nested);
+
+    if rest.is_empty() {
+        let inherent_impls = definitions
+            .iter()
+            .filter(|(_, kind)| *kind == Kind::InherentImpl)
+            .map(|(range, _)| range.clone());
+        return match definitions.iter().find(|(_, kind)| *kind == Kind::Item) {
+            Some((item, _)) => Some(
+                std::iter::once(item.clone())
+                    .chain(inherent_impls)
+                    .collect(),
+            ),
+            None => {
+                let impls = inherent_impls.collect::<Vec<_>>();
+                if impls.is_empty() {
+                    definitions.first().map(|(range, _)| vec![range.clone()])
+                } else {
+                    Some(impls)
+                }
+            }
+        };
+    }
+
+    // トレイトの実装も含め、すべての定義とimplブロックの中を探す
+    definitions.iter().find_map(|(definition, _)| {
+        let body = definition.start + 1..definition.end;
+        find_nested(lines, syntax, body, rest, true)
+    })
+}
+
+/// 範囲内にある`name`の定義の行範囲を、出現順に返す（Rustの`impl`ブロックは定義の後）
+fn find_definitions(
+    lines: &[&str],
+    syntax: Syntax,
+    range: Range<usize>,
+    name: &str,
+    nested: bool,
+) -> Vec<(Range<usize>, Kind)> {
+    let patterns = syntax.definition_patterns(name, nested);
+    let find = |kind_of: &dyn Fn(&str) -> Option<Kind>| {
+        range
+            .clone()
+            .filter_map(|i| {
+                let kind = kind_of(lines[i])?;
+                Some((i..definition_end(lines, syntax, i).min(range.end), kind))
+            })
+            .collect::<Vec<_>>()
+    };
+
+    let mut definitions = find(&|line| {
+        patterns
+            .iter()
+            .any(|regex| regex.is_match(line))
+            .then_some(Kind::Item)
+    });
+    if let Some(impl_pattern) = syntax.impl_pattern() {
+        definitions.extend(find(&|line| {
+            let captures = impl_pattern.captures(line)?;
+            (&captures["name"] == name).then(|| match captures.name("trait") {
+                Some(_) => Kind::TraitImpl,
+                None => Kind::InherentImpl,
+            })
+        }));
+    }
+
+    definitions
+}
+
+/// 定義の終わり（最終行の次の行番号、0始まり）を返す
+fn definition_end(lines: &[&str], syntax: Syntax, start: usize) -> usize {
+    match syntax {
+        Syntax::Python => indented_block_end(lines, start),
+        Syntax::Rust | Syntax::Script => braced_block_end(lines, syntax, start),
+    }
+}
+
+/// 波括弧の対応から定義の終わりを求める
+///
+/// `{`より前に括弧の外で`;`が現れた場合（`struct Unit;`など）はその行で終わる。
+fn braced_block_end(lines: &[&str], syntax: Syntax, start: usize) -> usize {
+    let mut depth = 0usize;
+    let mut opened = false;
+    let mut in_block_comment = false;
+
+    for (i, line) in lines.iter().enumerate().skip(start) {
+        let mut chars = line.chars().peekable();
+        while let Some(c) = chars.next() {
+            if in_block_comment {
+                if c == '*' && chars.peek() == Some(&'/') {
+                    chars.next();
+                    in_block_comment = false;
+                }
+                continue;
+            }
+
+            match c {
+                '/' if chars.peek() == Some(&'/') => break,
+                '/' if chars.peek() == Some(&'*') => {
+                    chars.next();
+                    in_block_comment = true;
+                }
+                '"' | '`' => skip_string(&mut chars, c),
+                '\'' if syntax == Syntax::Script => skip_string(&mut chars, c),
+                // Rustの`'`はライフタイムのこともあるため、文字リテラルの形のときだけ読み飛ばす
+                '\'' => {
+                    let rest = chars.clone().collect::<String>();
+                    if rest.starts_with('\\') || rest.chars().nth(1) == Some('\'') {
+                        skip_string(&mut chars, c);
+                    }
+                }
+                '{' | '(' | '[' => {
+                    opened |= c == '{';
+                    depth += 1;
+                }
+                '}' | ')' | ']' => {
+                    depth = depth.saturating_sub(1);
+                    if opened && depth == 0 {
+                        return i + 1;
+                    }
+                }
+                ';' if depth == 0 => return i + 1,
+                _ => {}
+            }
+        }
+    }
+
+    lines.len()
+}
+
+fn skip_string(chars: &mut std::iter::Peekable<std::str::Chars>, quote: char) {
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' => {
+                chars.next();
+            }
+            c if c == quote => return,
+            _ => {}
+        }
+    }
+}
+
+/// インデントから定義の終わりを求める
+fn indented_block_end(lines: &[&str], start: usize) -> usize {
+    let indent = indent_of(lines[start]);
+    let mut end = start + 1;
+
+    for (i, line) in lines.iter().enumerate().skip(start + 1) {
+        if line.trim().is_empty() {
+            continue;
+        }
+        // 閉じ括弧だけの行は、複数行にわたる引数リストの続き
+        let closes_header = i == end && line.trim_start().starts_with(')');
+        if indent_of(line) <= indent && !closes_header {
+            break;
+        }
+        end = i + 1;
+    }
+
+    end
+}
+
+fn indent_of(line: &str) -> usize {
+    line.len() - line.trim_start().len()
+}
+
+/// 定義の直前にあるドキュメントコメント・属性・デコレーターの先頭行を返す
+fn leading_comments_start(lines: &[&str], syntax: Syntax, start: usize) -> usize {
+    let mut result = start;
+    let mut in_block_comment = false;
+
+    for i in (0..start).rev() {
+        let line = lines[i].trim();
+
+        if in_block_comment {
+            if line.starts_with("/*") {
+                in_block_comment = false;
+                result = i;
+            }
+            continue;
+        }
+
+        let included = match syntax {
+            Syntax::Rust => line.starts_with("///") || line.starts_with("#["),
+            Syntax::Script => line.starts_with('@'),
+            Syntax::Python => line.starts_with('@') || line.starts_with('#'),
+        };
+
+        if included {
+            result = i;
+        } else if syntax != Syntax::Python && line.ends_with("*/") {
+            if line.starts_with("/*") {
+                result = i;
+            } else {
+                in_block_comment = true;
+            }
+        } else {
+            break;
+        }
+    }
+
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const RUST: &str = r#"use std::fmt;
+
+/// 設定
+#[derive(Debug)]
+pub struct Config {
+    name: String,
+}
+
+impl Config {
+    /// 作成する
+    pub fn new(name: &str) -> Self {
+        let braces = "}";
+        Self { name: name.to_string() }
+    }
+}
+
+impl fmt::Display for Config {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.name)
+    }
+}
+
+pub struct Unit;
+
+impl Default for Unit {
+    fn default() -> Self {
+        Unit
+    }
+}
+"#;
+
+    #[test]
+    fn test_rust_items() {
+        // 型にはinherent implブロックを含め、トレイトの実装は含めない
+        let extracted = extract(RUST, "rust", "Config").unwrap();
+        assert!(extracted
+            .content
+            .starts_with("/// 設定\n#[derive(Debug)]\npub struct Config {\n    name: String,\n}\n\nimpl Config {\n"));
+        assert!(!extracted.content.contains("Display"));
+        assert_eq!((extracted.start_line, extracted.end_line), (3, 15));
+
+        let extracted = extract(RUST, "rust", "Unit").unwrap();
+        assert_eq!(extracted.content, "pub struct Unit;");
+    }
+
+    #[test]
+    fn test_rust_nested() {
+        let extracted = extract(RUST, "rust", "Config::new").unwrap();
+        assert!(extracted
+            .content
+            .starts_with("    /// 作成する\n    pub fn new"));
+        assert!(extracted.content.ends_with("    }"));
+
+        // トレイトの実装のメソッド
+        let extracted = extract(RUST, "rust", "Config::fmt").unwrap();
+        assert!(extracted.content.contains("write!"));
+        let extracted = extract(RUST, "rust", "Unit::default").unwrap();
+        assert_eq!(
+            extracted.content,
+            "    fn default() -> Self {\n        Unit\n    }"
+        );
+    }
+
+    #[test]
+    fn test_typescript() {
+        let content = r#"import x from "y";
+
+/**
+ * ユーザー
+ */
+@Entity()
+export class User {
+  constructor(private name: string) {}
+
+  greet(): string {
+    return `hello ${this.name}`;
+  }
+}
+
+export interface Props {
+  name: string;
+}
+
+export const add = (a: number, b: number) => {
+  return a + b;
+};
+"#;
+
+        let extracted = extract(content, "typescript", "User").unwrap();
+        assert!(extracted
+            .content
+            .starts_with("/**\n * ユーザー\n */\n@Entity()"));
+        assert!(extracted.content.ends_with("}"));
+        assert_eq!(extracted.end_line, 13);
+
+        let extracted = extract(content, "typescript", "User::greet").unwrap();
+        assert_eq!(
+            extracted.content,
+            "  greet(): string {\n    return `hello ${this.name}`;\n  }"
+        );
+
+        let extracted = extract(content, "typescript", "Props").unwrap();
+        assert_eq!(
+            extracted.content,
+            "export interface Props {\n  name: string;\n}"
+        );
+
+        let extracted = extract(content, "typescript", "add").unwrap();
+        assert!(extracted.content.ends_with("};"));
+    }
+
+    #[test]
+    fn test_python() {
+        let content = r#"import os
+
+# 挨拶する
+@decorator
+def greet(
+    name,
+):
+    """docstring"""
+    print(name)
+
+    return name
+
+
+class User:
+    def __init__(self):
+        self.name = ""
+
+    def greet(self):
+        return self.name
+"#;
+
+        let extracted = extract(content, "python", "greet").unwrap();
+        assert!(extracted
+            .content
+            .starts_with("# 挨拶する\n@decorator\ndef greet("));
+        assert!(extracted.content.ends_with("    return name"));
+
+        let extracted = extract(content, "python", "User::greet").unwrap();
+        assert_eq!(
+            extracted.content,
+            "    def greet(self):\n        return self.name"
+        );
+    }
+
+    #[test]
+    fn test_errors() {
+        assert_eq!(extract(RUST, "rust", "Missing"), Err(SymbolError::NotFound));
+        assert_eq!(
+            extract("", "haskell", "main"),
+            Err(SymbolError::UnsupportedLanguage)
+        );
+    }
+}
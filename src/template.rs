@@ -1,15 +1,90 @@
-use crate::error::Result;
-use std::fs;
-use std::path::Path;
+use crate::arguments::{self, Options};
+use crate::error::{Error, Location, Result, UndefinedVariable};
+use crate::extract::LineRange;
+use crate::front_matter::{self, FrontMatter};
+use crate::git::{DiffOptions, RevisionPath};
+use once_cell::sync::Lazy;
+use regex::{Captures, Regex};
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use std::{env, fs};
+
+/// `{{name}}`形式の変数参照（`\{{name}}`はエスケープ）
+static VARIABLE: Lazy<Regex> =
+    Lazy::new(|| Regex::new(r"\\?\{\{\s*([A-Za-z_][\w-]*)\s*\}\}").unwrap());
+static VARIABLE_NAME: Lazy<Regex> = Lazy::new(|| Regex::new(r"^[A-Za-z_][\w-]*$").unwrap());
+
+/// 引数を省略できる指示子
+const OPTIONAL_ARGUMENT_DIRECTIVES: &[&str] = &["efdiff", "efchanged"];
 
 #[derive(Debug, PartialEq)]
 pub enum Directive {
     Glob(String),
     Regex(String),
+    /// 以降の指示子で展開対象から除外するパターン（`#efx`）
+    Exclude(String),
+    /// 行範囲を指定した埋め込み（`#ef path:10-40`）
+    Lines {
+        pattern: String,
+        range: LineRange,
+    },
+    /// コメントのマーカーで囲まれた領域の埋め込み（`#efregion path#name`）
+    Region {
+        pattern: String,
+        name: String,
+    },
+    /// 関数・型・クラスなどの定義の埋め込み（`#efsym path::Symbol`）
+    Symbol {
+        pattern: String,
+        symbol: String,
+    },
+    /// Rustのモジュールツリーを構成するファイルの埋め込み（`#efmod src/lib.rs depth=2`）
+    ModuleTree {
+        root: String,
+        depth: Option<usize>,
+    },
+    /// importをたどったファイルの埋め込み（`#efdeps src/App.tsx depth=2`）
+    Dependencies {
+        entry: String,
+        depth: Option<usize>,
+    },
+    /// Rustのアイテムパスで指定した定義の埋め込み（`#efrs crate::module::Item`）
+    RustItem {
+        item: String,
+        /// クレートのルートファイル（省略時はCargo.tomlから探す）
+        root: Option<PathBuf>,
+        /// `#[cfg(test)]`の要素を含めるか
+        include_tests: bool,
+    },
+    /// コマンドの出力の埋め込み（`#efcmd cargo test`）
+    Command(String),
+    /// ディレクトリツリーの埋め込み（`#eftree src depth=2`）
+    Tree {
+        root: String,
+        depth: Option<usize>,
+        /// `.`で始まるファイル・ディレクトリも含めるか
+        include_hidden: bool,
+        /// ファイルサイズを付けるか
+        sizes: bool,
+        /// 行数を付けるか
+        line_counts: bool,
+    },
+    /// gitのリビジョンでのファイルの埋め込み（`#ef git:HEAD~3:src/main.rs`、`#ef src/main.rs rev=HEAD~3`）
+    Revision(Vec<RevisionPath>),
+    /// gitの差分の埋め込み（`#efdiff main..HEAD -- src`）
+    Diff(DiffOptions),
+    /// gitのリビジョンから変更されたファイルの埋め込み（`#efchanged main`）
+    Changed {
+        /// 比較するリビジョン（省略時はHEAD）
+        base: Option<String>,
+        /// 追跡されていないファイルも含めるか
+        include_untracked: bool,
+    },
 }
 
 #[derive(Debug, PartialEq)]
 pub struct Template {
+    front_matter: FrontMatter,
     lines: Vec<TemplateLine>,
 }
 
@@ -20,52 +95,309 @@ pub enum TemplateLine {
 }
 
 impl Template {
-    pub fn from_file<P: AsRef<Path>>(path: P) -> Result<Self> {
-        let content = fs::read_to_string(path)?;
-        Self::parse(&content)
-    }
-
-    pub fn parse(content: &str) -> Result<Self> {
-        let mut lines = Vec::new();
-
-        for (_i, line) in content.lines().enumerate() {
-            let line = if let Some(parts) = line.strip_prefix('#') {
-                let mut elements = parts.split_whitespace();
-
-                if let Some(directive_name) = elements.next() {
-                    let arguments = elements.collect::<Vec<_>>();
-
-                    match directive_name {
-                        "ef" => {
-                            let argument = arguments.join(" ");
-                            if argument.is_empty() {
-                                TemplateLine::Text(line.to_string())
-                            } else {
-                                TemplateLine::Directive(Directive::Glob(argument))
-                            }
-                        }
-                        "efr" => {
-                            let argument = arguments.join(" ");
-                            if argument.is_empty() {
-                                TemplateLine::Text(line.to_string())
-                            } else {
-                                TemplateLine::Directive(Directive::Regex(argument))
-                            }
-                        }
-                        _ => TemplateLine::Text(line.to_string()),
-                    }
-                } else {
-                    // #だけの行の場合
-                    TemplateLine::Text(line.to_string())
-                }
-            } else {
-                // #で始まらない行の場合
-                TemplateLine::Text(line.to_string())
-            };
-            lines.push(line);
+    /// テンプレートファイルを読み込む
+    ///
+    /// `#efinclude`で指定したファイルは、このファイルのディレクトリを起点に解決して展開する。
+    /// `defines`はコマンドラインで指定した変数で、テンプレート内の`#efset`より優先する。
+    pub fn from_file<P: AsRef<Path>>(path: P, defines: &BTreeMap<String, String>) -> Result<Self> {
+        let mut parser = Parser {
+            defines: defines.clone(),
+            ..Parser::default()
+        };
+        let front_matter = parser.parse_file(path.as_ref())?;
+        parser.finish(front_matter)
+    }
+
+    /// テスト用に、文字列のテンプレートを解釈する
+    ///
+    /// `#efinclude`や`exists()`を使うテストは、一時ディレクトリと`from_file`を使う。
+    #[cfg(test)]
+    fn parse(content: &str) -> Result<Self> {
+        let mut parser = Parser::default();
+        let front_matter = parser.parse_content(content, None)?;
+        parser.finish(front_matter)
+    }
+
+    /// 1行を解釈する
+    fn parse_line(line: &str) -> Result<TemplateLine> {
+        let Some(parts) = line.strip_prefix('#') else {
+            // #で始まらない行の場合
+            return Ok(TemplateLine::Text(line.to_string()));
+        };
+        let parts = parts.trim();
+
+        let Some(directive_name) = parts.split_whitespace().next() else {
+            // #だけの行の場合
+            return Ok(TemplateLine::Text(line.to_string()));
+        };
+        let argument = parts[directive_name.len()..].trim().to_string();
+
+        Ok(match Self::parse_directive(directive_name, argument)? {
+            Some(directive) => TemplateLine::Directive(directive),
+            None => TemplateLine::Text(line.to_string()),
+        })
+    }
+
+    /// 指示子として解釈できない場合（未知の指示子、引数なし）は`None`を返す
+    fn parse_directive(name: &str, argument: String) -> Result<Option<Directive>> {
+        if argument.is_empty() && !OPTIONAL_ARGUMENT_DIRECTIVES.contains(&name) {
+            return Ok(None);
+        }
+
+        let directive = match name {
+            "ef" => Self::parse_glob(argument)?,
+            "efr" => Directive::Regex(argument),
+            "efx" => Directive::Exclude(argument),
+            "efregion" => Self::parse_region(argument)?,
+            "efsym" => Self::parse_symbol(argument)?,
+            "efrs" => Self::parse_rust_item(&argument)?,
+            "efmod" => Self::parse_module_tree(&argument)?,
+            "efdeps" => Self::parse_dependencies(&argument)?,
+            "efcmd" => Directive::Command(argument),
+            "eftree" => Self::parse_tree(&argument)?,
+            "efdiff" => Self::parse_diff(&argument)?,
+            "efchanged" => Self::parse_changed(&argument)?,
+            _ => return Ok(None),
+        };
+
+        Ok(Some(directive))
+    }
+
+    /// `#ef`の引数を解釈する
+    ///
+    /// パスが1つだけで`:10-40`のような行範囲が付いている場合は`Directive::Lines`になる。
+    /// `git:`で始まるパスや`rev=`オプションがある場合は`Directive::Revision`になる。
+    fn parse_glob(argument: String) -> Result<Directive> {
+        let terms = match arguments::split(&argument) {
+            Ok(terms) => terms,
+            // 引数の構文エラーはPathResolverで報告する
+            Err(_) => return Ok(Directive::Glob(argument)),
+        };
+
+        if let Some(paths) = Self::parse_revision_paths(&argument, &terms)? {
+            return Ok(Directive::Revision(paths));
+        }
+
+        let [term] = terms.as_slice() else {
+            return Ok(Directive::Glob(argument));
+        };
+
+        match LineRange::split_suffix(term) {
+            Some((pattern, Ok(range))) => Ok(Directive::Lines {
+                pattern: pattern.to_string(),
+                range,
+            }),
+            Some((_, Err(message))) => Err(Error::InvalidDirectiveArgument { argument, message }),
+            None => Ok(Directive::Glob(argument)),
+        }
+    }
+
+    /// `#ef`の引数がgitのリビジョンを指定していればパスの一覧を返す
+    fn parse_revision_paths(argument: &str, terms: &[String]) -> Result<Option<Vec<RevisionPath>>> {
+        let invalid = |message: &str| Error::InvalidDirectiveArgument {
+            argument: argument.to_string(),
+            message: message.to_string(),
+        };
+
+        let (revisions, patterns): (Vec<_>, Vec<_>) =
+            terms.iter().partition(|term| term.starts_with("rev="));
+        if let Some(revision) = revisions.first() {
+            let revision = &revision["rev=".len()..];
+            if revisions.len() > 1 || revision.is_empty() {
+                return Err(invalid("expected a single `rev=<revision>`"));
+            }
+            if patterns.is_empty() || patterns.iter().any(|term| term.starts_with("git:")) {
+                return Err(invalid("`rev=` needs paths without the `git:` prefix"));
+            }
+            return Ok(Some(
+                patterns
+                    .into_iter()
+                    .map(|pattern| RevisionPath {
+                        revision: revision.to_string(),
+                        pattern: pattern.clone(),
+                    })
+                    .collect(),
+            ));
+        }
+
+        if !terms.iter().any(|term| term.starts_with("git:")) {
+            return Ok(None);
+        }
+        terms
+            .iter()
+            .map(|term| {
+                let (revision, pattern) = term
+                    .strip_prefix("git:")
+                    .and_then(|spec| spec.split_once(':'))
+                    .filter(|(revision, pattern)| !revision.is_empty() && !pattern.is_empty())
+                    .ok_or_else(|| invalid("expected `git:<revision>:<path>` for every path"))?;
+                Ok(RevisionPath {
+                    revision: revision.to_string(),
+                    pattern: pattern.to_string(),
+                })
+            })
+            .collect::<Result<Vec<_>>>()
+            .map(Some)
+    }
+
+    /// `#efregion`の引数（`path#name`）を解釈する
+    fn parse_region(argument: String) -> Result<Directive> {
+        let invalid = |message: &str| Error::InvalidDirectiveArgument {
+            argument: argument.clone(),
+            message: message.to_string(),
+        };
+
+        let terms = arguments::split(&argument)?;
+        let [term] = terms.as_slice() else {
+            return Err(invalid("expected a single `path#name`"));
+        };
+
+        match term.rsplit_once('#') {
+            Some((pattern, name)) if !pattern.is_empty() && !name.is_empty() => {
+                Ok(Directive::Region {
+                    pattern: pattern.to_string(),
+                    name: name.to_string(),
+                })
+            }
+            _ => Err(invalid("expected `path#name`")),
         }
+    }
+
+    /// `#efsym`の引数（`path::Symbol`）を解釈する
+    fn parse_symbol(argument: String) -> Result<Directive> {
+        let invalid = |message: &str| Error::InvalidDirectiveArgument {
+            argument: argument.clone(),
+            message: message.to_string(),
+        };
 
-        Ok(Self { lines })
+        let terms = arguments::split(&argument)?;
+        let [term] = terms.as_slice() else {
+            return Err(invalid("expected a single `path::Symbol`"));
+        };
+
+        match term.split_once("::") {
+            Some((pattern, symbol)) if !pattern.is_empty() && !symbol.is_empty() => {
+                Ok(Directive::Symbol {
+                    pattern: pattern.to_string(),
+                    symbol: symbol.to_string(),
+                })
+            }
+            _ => Err(invalid("expected `path::Symbol`")),
+        }
+    }
+
+    /// `#efrs`の引数（`crate::module::Item [tests=false] [root=src/lib.rs]`）を解釈する
+    fn parse_rust_item(argument: &str) -> Result<Directive> {
+        let (terms, mut options) = Options::parse(argument)?;
+        let [item] = terms.as_slice() else {
+            return Err(options.error("expected a single item path".to_string()));
+        };
+        if item != "crate" && !item.starts_with("crate::") {
+            return Err(options.error("item path must start with `crate::`".to_string()));
+        }
+
+        let directive = Directive::RustItem {
+            item: item.clone(),
+            root: options.take_string("root").map(PathBuf::from),
+            include_tests: options.take_bool("tests")?.unwrap_or(true),
+        };
+        options.finish()?;
+        Ok(directive)
+    }
+
+    /// `#efmod`の引数（`src/lib.rs [depth=N]`）を解釈する
+    fn parse_module_tree(argument: &str) -> Result<Directive> {
+        let (terms, mut options) = Options::parse(argument)?;
+        let [root] = terms.as_slice() else {
+            return Err(options.error("expected a single crate root file".to_string()));
+        };
+
+        let directive = Directive::ModuleTree {
+            root: root.clone(),
+            depth: options.take_usize("depth")?,
+        };
+        options.finish()?;
+        Ok(directive)
+    }
+
+    /// `#efdeps`の引数（`src/App.tsx [depth=N]`）を解釈する
+    fn parse_dependencies(argument: &str) -> Result<Directive> {
+        let (terms, mut options) = Options::parse(argument)?;
+        let [entry] = terms.as_slice() else {
+            return Err(options.error("expected a single entry file".to_string()));
+        };
+
+        let directive = Directive::Dependencies {
+            entry: entry.clone(),
+            depth: options.take_usize("depth")?,
+        };
+        options.finish()?;
+        Ok(directive)
+    }
+
+    /// `#eftree`の引数（ディレクトリと`depth=N`、`hidden=`、`size=`、`lines=`）を解釈する
+    fn parse_tree(argument: &str) -> Result<Directive> {
+        let (terms, mut options) = Options::parse(argument)?;
+        let [root] = terms.as_slice() else {
+            return Err(options.error("expected a single directory".to_string()));
+        };
+
+        let directive = Directive::Tree {
+            root: root.clone(),
+            depth: options.take_usize("depth")?,
+            include_hidden: options.take_bool("hidden")?.unwrap_or(false),
+            sizes: options.take_bool("size")?.unwrap_or(false),
+            line_counts: options.take_bool("lines")?.unwrap_or(false),
+        };
+        options.finish()?;
+        Ok(directive)
+    }
+
+    /// `#efdiff`の引数（`[<rev-range>] [-- <pathspec>...]`と`context=N`、`staged=`）を解釈する
+    fn parse_diff(argument: &str) -> Result<Directive> {
+        let (terms, mut options) = Options::parse(argument)?;
+        let (revisions, pathspecs) = match terms.iter().position(|term| term == "--") {
+            Some(index) => (&terms[..index], terms[index + 1..].to_vec()),
+            None => (terms.as_slice(), Vec::new()),
+        };
+        let revisions = match revisions {
+            [] => None,
+            [revisions] => Some(revisions.clone()),
+            _ => {
+                return Err(options
+                    .error("expected a single revision range (put paths after `--`)".to_string()))
+            }
+        };
+
+        let directive = Directive::Diff(DiffOptions {
+            revisions,
+            pathspecs,
+            context: options.take_usize("context")?,
+            staged: options.take_bool("staged")?.unwrap_or(false),
+        });
+        options.finish()?;
+        Ok(directive)
+    }
+
+    /// `#efchanged`の引数（`[<base-ref>]`と`untracked=`）を解釈する
+    fn parse_changed(argument: &str) -> Result<Directive> {
+        let (terms, mut options) = Options::parse(argument)?;
+        let base = match terms.as_slice() {
+            [] => None,
+            [base] => Some(base.clone()),
+            _ => return Err(options.error("expected a single base revision".to_string())),
+        };
+
+        let directive = Directive::Changed {
+            base,
+            include_untracked: options.take_bool("untracked")?.unwrap_or(false),
+        };
+        options.finish()?;
+        Ok(directive)
+    }
+
+    pub fn front_matter(&self) -> &FrontMatter {
+        &self.front_matter
     }
 
     pub fn lines(&self) -> &[TemplateLine] {
@@ -73,6 +405,341 @@ impl Template {
     }
 }
 
+/// 変数名として使える文字列か（英字か`_`で始まり、英数字・`_`・`-`が続く）
+pub(crate) fn is_variable_name(name: &str) -> bool {
+    VARIABLE_NAME.is_match(name)
+}
+
+/// `#efinclude`と変数を展開しながらテンプレートを読み込む
+#[derive(Default)]
+struct Parser {
+    /// 読み込み中のファイル（外側から順）。includeの循環の検出に使う
+    chain: Vec<PathBuf>,
+    lines: Vec<TemplateLine>,
+    /// コマンドラインで指定した変数
+    defines: BTreeMap<String, String>,
+    /// `#efset`とフロントマターで定義した変数
+    variables: BTreeMap<String, String>,
+    /// `#efset`の行があったか
+    has_set: bool,
+    /// 定義されていない変数の参照
+    undefined: Vec<UndefinedVariable>,
+}
+
+impl Parser {
+    fn parse_file(&mut self, path: &Path) -> Result<FrontMatter> {
+        let path = path
+            .canonicalize()
+            .map_err(|source| Error::TemplateNotFound {
+                path: path.to_path_buf(),
+                source,
+            })?;
+
+        if self.chain.contains(&path) {
+            let mut chain = self.chain.clone();
+            chain.push(path);
+            return Err(Error::IncludeCycle { chain });
+        }
+
+        let content = fs::read_to_string(&path)?;
+        self.chain.push(path.clone());
+        let front_matter = self.parse_content(&content, path.parent())?;
+        self.chain.pop();
+
+        Ok(front_matter)
+    }
+
+    fn parse_content(&mut self, content: &str, base_dir: Option<&Path>) -> Result<FrontMatter> {
+        let (front_matter, body) = front_matter::split(content)?;
+        // 最上位のテンプレートのフロントマターの変数は、`#efset`の初期値にする
+        if self.chain.len() <= 1 {
+            self.variables.extend(front_matter.variables.clone());
+        }
+        // フロントマターの分だけ行番号をずらす
+        let first_line = content[..content.len() - body.len()].lines().count() + 1;
+
+        let mut blocks: Vec<Block> = Vec::new();
+        for (index, line) in body.lines().enumerate() {
+            let location = Location {
+                file: self.chain.last().cloned(),
+                line: first_line + index,
+            };
+
+            if let Some((keyword, argument)) = Self::block_directive(line) {
+                self.enter_block(&mut blocks, keyword, argument, location, base_dir)?;
+                continue;
+            }
+            // 条件が成り立たないブロックの中は、変数の参照も含めて読み飛ばす
+            if !blocks.last().is_none_or(Block::is_active) {
+                continue;
+            }
+
+            let line = self.interpolate(line, &location);
+            let line = line.as_str();
+
+            if let Some((name, value)) = Self::set_argument(line)? {
+                self.has_set = true;
+                // コマンドラインで指定した変数は上書きしない
+                if !self.defines.contains_key(&name) {
+                    self.variables.insert(name, value);
+                }
+                continue;
+            }
+
+            match Self::include_argument(line) {
+                Some(argument) => {
+                    let path = Self::include_path(argument)?;
+                    let path = match base_dir {
+                        Some(base_dir) => base_dir.join(path),
+                        None => path,
+                    };
+                    // 取り込んだテンプレートのフロントマターは使わない
+                    self.parse_file(&path)?;
+                }
+                None => self.lines.push(Template::parse_line(line)?),
+            }
+        }
+
+        // ブロックはファイルごとに閉じている必要がある
+        if let Some(block) = blocks.pop() {
+            return Err(Error::TemplateSyntax {
+                location: block.location,
+                message: "#efif is not closed by #efendif".to_string(),
+            });
+        }
+
+        Ok(front_matter)
+    }
+
+    /// `#efif`・`#efelse`・`#efendif`の行なら指示子名と引数を返す
+    fn block_directive(line: &str) -> Option<(&str, &str)> {
+        let parts = line.strip_prefix('#')?.trim();
+        let name = parts.split_whitespace().next()?;
+        matches!(name, "efif" | "efelse" | "efendif").then(|| (name, parts[name.len()..].trim()))
+    }
+
+    fn enter_block(
+        &mut self,
+        blocks: &mut Vec<Block>,
+        keyword: &str,
+        argument: &str,
+        location: Location,
+        base_dir: Option<&Path>,
+    ) -> Result<()> {
+        let syntax_error = |location: Location, message: &str| Error::TemplateSyntax {
+            location,
+            message: message.to_string(),
+        };
+
+        match keyword {
+            "efif" => {
+                if argument.is_empty() {
+                    return Err(syntax_error(location, "#efif requires a condition"));
+                }
+                let parent_active = blocks.last().is_none_or(Block::is_active);
+                // 外側のブロックが無効なら条件は評価しない
+                let condition = parent_active && {
+                    let argument = self.interpolate(argument, &location);
+                    self.evaluate(&argument, base_dir)
+                        .map_err(|message| syntax_error(location.clone(), &message))?
+                };
+                blocks.push(Block {
+                    location,
+                    parent_active,
+                    condition,
+                    in_else: false,
+                });
+            }
+            "efelse" => match blocks.last_mut() {
+                Some(block) if !block.in_else => block.in_else = true,
+                Some(_) => return Err(syntax_error(location, "duplicate #efelse")),
+                None => return Err(syntax_error(location, "#efelse without #efif")),
+            },
+            _ => {
+                if blocks.pop().is_none() {
+                    return Err(syntax_error(location, "#efendif without #efif"));
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// `#efif`の条件を評価する
+    ///
+    /// - `name`: 変数が定義されていて空でない
+    /// - `env(NAME)`: 環境変数が設定されていて空でない
+    /// - `name == value`、`name != value`: 値の比較（左辺には`env(NAME)`も書ける）
+    /// - `exists(path)`: ファイル・ディレクトリが存在する（テンプレートのディレクトリを起点に解決）
+    /// - `!condition`: 否定
+    fn evaluate(
+        &self,
+        condition: &str,
+        base_dir: Option<&Path>,
+    ) -> std::result::Result<bool, String> {
+        let condition = condition.trim();
+        if let Some(negated) = condition.strip_prefix('!') {
+            return Ok(!self.evaluate(negated, base_dir)?);
+        }
+
+        for (operator, equal) in [("==", true), ("!=", false)] {
+            if let Some((left, right)) = condition.split_once(operator) {
+                let value = self.operand(left.trim())?;
+                return Ok((value.as_deref() == Some(unquote(right.trim()))) == equal);
+            }
+        }
+
+        if let Some(path) = call(condition, "exists") {
+            let path = PathBuf::from(unquote(path.trim()));
+            let path = match base_dir {
+                Some(base_dir) => base_dir.join(path),
+                None => path,
+            };
+            return Ok(path.exists());
+        }
+
+        Ok(self
+            .operand(condition)?
+            .is_some_and(|value| !value.is_empty()))
+    }
+
+    /// 条件の左辺（変数か`env(NAME)`）の値を返す
+    fn operand(&self, operand: &str) -> std::result::Result<Option<String>, String> {
+        if let Some(name) = call(operand, "env") {
+            return Ok(env::var(name.trim()).ok());
+        }
+        if is_variable_name(operand) {
+            return Ok(self
+                .defines
+                .get(operand)
+                .or_else(|| self.variables.get(operand))
+                .cloned());
+        }
+        Err(format!("invalid condition `{operand}`"))
+    }
+
+    /// 変数を使うテンプレート（`#efset`か`-D`かフロントマターの`variables`がある）でだけ、定義されていない変数をエラーにする
+    ///
+    /// 変数を使わないテンプレートでは、`{{ title }}`のようなVueやJinjaの記法をそのまま出力する。
+    fn finish(self, front_matter: FrontMatter) -> Result<Template> {
+        let uses_variables =
+            self.has_set || !self.defines.is_empty() || !front_matter.variables.is_empty();
+        if uses_variables && !self.undefined.is_empty() {
+            return Err(Error::UndefinedVariables {
+                variables: self.undefined,
+            });
+        }
+        Ok(Template {
+            front_matter,
+            lines: self.lines,
+        })
+    }
+
+    /// `{{name}}`を変数の値に置き換える
+    ///
+    /// 定義されていない変数はそのまま残し、エラーの報告用に位置を記録する。
+    fn interpolate(&mut self, line: &str, location: &Location) -> String {
+        VARIABLE
+            .replace_all(line, |captures: &Captures| {
+                let reference = &captures[0];
+                if let Some(escaped) = reference.strip_prefix('\\') {
+                    return escaped.to_string();
+                }
+
+                let name = &captures[1];
+                match self.defines.get(name).or_else(|| self.variables.get(name)) {
+                    Some(value) => value.clone(),
+                    None => {
+                        self.undefined.push(UndefinedVariable {
+                            name: name.to_string(),
+                            location: location.clone(),
+                        });
+                        reference.to_string()
+                    }
+                }
+            })
+            .into_owned()
+    }
+
+    /// `#efset name = value`の行なら変数名と値を返す
+    fn set_argument(line: &str) -> Result<Option<(String, String)>> {
+        let Some(argument) = line
+            .strip_prefix('#')
+            .and_then(|parts| parts.trim().strip_prefix("efset"))
+            .filter(|argument| argument.starts_with(char::is_whitespace))
+        else {
+            return Ok(None);
+        };
+
+        let error = |message: &str| Error::InvalidDirectiveArgument {
+            argument: argument.trim().to_string(),
+            message: message.to_string(),
+        };
+        let (name, value) = argument
+            .split_once('=')
+            .ok_or_else(|| error("expected `name = value`"))?;
+        let name = name.trim();
+        if !is_variable_name(name) {
+            return Err(error("invalid variable name"));
+        }
+
+        Ok(Some((name.to_string(), unquote(value.trim()).to_string())))
+    }
+
+    /// `#efinclude`の行なら引数を返す
+    fn include_argument(line: &str) -> Option<&str> {
+        let parts = line.strip_prefix('#')?.trim();
+        let argument = parts.strip_prefix("efinclude")?;
+        (argument.is_empty() || argument.starts_with(char::is_whitespace))
+            .then(|| argument.trim())
+            .filter(|argument| !argument.is_empty())
+    }
+
+    fn include_path(argument: &str) -> Result<PathBuf> {
+        match arguments::split(argument)?.as_slice() {
+            [path] => Ok(PathBuf::from(path)),
+            _ => Err(Error::InvalidDirectiveArgument {
+                argument: argument.to_string(),
+                message: "expected a single template file".to_string(),
+            }),
+        }
+    }
+}
+
+/// `#efif`のブロック
+struct Block {
+    /// `#efif`の位置
+    location: Location,
+    /// 外側のブロックが有効か
+    parent_active: bool,
+    condition: bool,
+    /// `#efelse`以降か
+    in_else: bool,
+}
+
+impl Block {
+    /// ブロック内の行を出力するか
+    fn is_active(&self) -> bool {
+        self.parent_active && self.condition != self.in_else
+    }
+}
+
+/// `function(argument)`の形なら引数を返す
+fn call<'a>(expression: &'a str, function: &str) -> Option<&'a str> {
+    expression
+        .strip_prefix(function)?
+        .trim_start()
+        .strip_prefix('(')?
+        .strip_suffix(')')
+}
+
+/// 値全体を囲む引用符を外す
+fn unquote(value: &str) -> &str {
+    ['"', '\'']
+        .into_iter()
+        .find_map(|quote| value.strip_prefix(quote)?.strip_suffix(quote))
+        .unwrap_or(value)
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -144,4 +811,457 @@ mod tests {
             ))]
         );
     }
+
+    #[test]
+    fn test_parse_exclude_directive() {
+        let template = Template::parse("#efx **/*.test.ts\n#ef src/**/*.ts !*.d.ts").unwrap();
+        assert_eq!(
+            template.lines(),
+            &[
+                TemplateLine::Directive(Directive::Exclude("**/*.test.ts".to_string())),
+                TemplateLine::Directive(Directive::Glob("src/**/*.ts !*.d.ts".to_string())),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_parse_line_range() {
+        let template =
+            Template::parse("#ef src/executor.rs:10-40\n#ef \"my file.rs\":100-").unwrap();
+        assert_eq!(
+            template.lines(),
+            &[
+                TemplateLine::Directive(Directive::Lines {
+                    pattern: "src/executor.rs".to_string(),
+                    range: LineRange {
+                        start: 10,
+                        end: Some(40)
+                    },
+                }),
+                TemplateLine::Directive(Directive::Lines {
+                    pattern: "my file.rs".to_string(),
+                    range: LineRange {
+                        start: 100,
+                        end: None
+                    },
+                }),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_parse_invalid_line_range() {
+        assert!(matches!(
+            Template::parse("#ef src/main.rs:40-10"),
+            Err(Error::InvalidDirectiveArgument { .. })
+        ));
+    }
+
+    #[test]
+    fn test_parse_region() {
+        let template = Template::parse("#efregion src/lib.rs#setup").unwrap();
+        assert_eq!(
+            template.lines(),
+            &[TemplateLine::Directive(Directive::Region {
+                pattern: "src/lib.rs".to_string(),
+                name: "setup".to_string(),
+            })]
+        );
+
+        assert!(matches!(
+            Template::parse("#efregion src/lib.rs"),
+            Err(Error::InvalidDirectiveArgument { .. })
+        ));
+    }
+
+    #[test]
+    fn test_parse_symbol() {
+        let template = Template::parse("#efsym src/lib.rs::Config::new").unwrap();
+        assert_eq!(
+            template.lines(),
+            &[TemplateLine::Directive(Directive::Symbol {
+                pattern: "src/lib.rs".to_string(),
+                symbol: "Config::new".to_string(),
+            })]
+        );
+
+        assert!(matches!(
+            Template::parse("#efsym src/lib.rs"),
+            Err(Error::InvalidDirectiveArgument { .. })
+        ));
+    }
+
+    #[test]
+    fn test_parse_rust_item() {
+        let template = Template::parse(
+            "#efrs crate::path_resolver::PathResolver\n#efrs crate::executor tests=false",
+        )
+        .unwrap();
+        assert_eq!(
+            template.lines(),
+            &[
+                TemplateLine::Directive(Directive::RustItem {
+                    item: "crate::path_resolver::PathResolver".to_string(),
+                    root: None,
+                    include_tests: true,
+                }),
+                TemplateLine::Directive(Directive::RustItem {
+                    item: "crate::executor".to_string(),
+                    root: None,
+                    include_tests: false,
+                }),
+            ]
+        );
+
+        assert!(Template::parse("#efrs path_resolver::PathResolver").is_err());
+        assert!(Template::parse("#efrs crate::a unknown=1").is_err());
+    }
+
+    #[test]
+    fn test_parse_module_tree() {
+        let template = Template::parse("#efmod src/lib.rs\n#efmod src/main.rs depth=1").unwrap();
+        assert_eq!(
+            template.lines(),
+            &[
+                TemplateLine::Directive(Directive::ModuleTree {
+                    root: "src/lib.rs".to_string(),
+                    depth: None,
+                }),
+                TemplateLine::Directive(Directive::ModuleTree {
+                    root: "src/main.rs".to_string(),
+                    depth: Some(1),
+                }),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_parse_dependencies() {
+        let template = Template::parse("#efdeps src/components/TaskList.tsx depth=2").unwrap();
+        assert_eq!(
+            template.lines(),
+            &[TemplateLine::Directive(Directive::Dependencies {
+                entry: "src/components/TaskList.tsx".to_string(),
+                depth: Some(2),
+            })]
+        );
+    }
+
+    #[test]
+    fn test_include() {
+        let dir = tempfile::tempdir().unwrap();
+        fs::create_dir_all(dir.path().join("shared")).unwrap();
+        fs::write(
+            dir.path().join("main.txt"),
+            "Before\n#efinclude shared/preamble.txt\nAfter",
+        )
+        .unwrap();
+        fs::write(
+            dir.path().join("shared/preamble.txt"),
+            "---\nformat: xml\n---\nConventions\n#ef src/*.rs\n#efinclude format.txt",
+        )
+        .unwrap();
+        fs::write(dir.path().join("shared/format.txt"), "Format").unwrap();
+
+        let template = Template::from_file(dir.path().join("main.txt"), &BTreeMap::new()).unwrap();
+        assert_eq!(template.front_matter(), &FrontMatter::default());
+        assert_eq!(
+            template.lines(),
+            &[
+                TemplateLine::Text("Before".to_string()),
+                TemplateLine::Text("Conventions".to_string()),
+                TemplateLine::Directive(Directive::Glob("src/*.rs".to_string())),
+                TemplateLine::Text("Format".to_string()),
+                TemplateLine::Text("After".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_include_cycle() {
+        let dir = tempfile::tempdir().unwrap();
+        fs::write(dir.path().join("a.txt"), "#efinclude b.txt").unwrap();
+        fs::write(dir.path().join("b.txt"), "#efinclude a.txt").unwrap();
+
+        let Err(Error::IncludeCycle { chain }) =
+            Template::from_file(dir.path().join("a.txt"), &BTreeMap::new())
+        else {
+            panic!("expected an include cycle");
+        };
+        let names = chain
+            .iter()
+            .map(|path| path.file_name().unwrap().to_str().unwrap())
+            .collect::<Vec<_>>();
+        assert_eq!(names, vec!["a.txt", "b.txt", "a.txt"]);
+    }
+
+    #[test]
+    fn test_parse_command_directive() {
+        let template = Template::parse("#efcmd cargo test -- --nocapture").unwrap();
+        assert_eq!(
+            template.lines(),
+            &[TemplateLine::Directive(Directive::Command(
+                "cargo test -- --nocapture".to_string()
+            ))]
+        );
+    }
+
+    #[test]
+    fn test_parse_tree_directive() {
+        let template = Template::parse("#eftree src depth=2 size=true\n#eftree .").unwrap();
+        assert_eq!(
+            template.lines(),
+            &[
+                TemplateLine::Directive(Directive::Tree {
+                    root: "src".to_string(),
+                    depth: Some(2),
+                    include_hidden: false,
+                    sizes: true,
+                    line_counts: false,
+                }),
+                TemplateLine::Directive(Directive::Tree {
+                    root: ".".to_string(),
+                    depth: None,
+                    include_hidden: false,
+                    sizes: false,
+                    line_counts: false,
+                }),
+            ]
+        );
+        assert!(Template::parse("#eftree src tests").is_err());
+        assert!(Template::parse("#eftree src depth=two").is_err());
+    }
+
+    #[test]
+    fn test_parse_revision_paths() {
+        let template = Template::parse(
+            "#ef git:HEAD~3:src/main.rs git:main:src/lib.rs\n#ef src/*.rs rev=v1.0",
+        )
+        .unwrap();
+        let revision_path = |revision: &str, pattern: &str| RevisionPath {
+            revision: revision.to_string(),
+            pattern: pattern.to_string(),
+        };
+        assert_eq!(
+            template.lines(),
+            &[
+                TemplateLine::Directive(Directive::Revision(vec![
+                    revision_path("HEAD~3", "src/main.rs"),
+                    revision_path("main", "src/lib.rs"),
+                ])),
+                TemplateLine::Directive(Directive::Revision(vec![revision_path(
+                    "v1.0", "src/*.rs"
+                )])),
+            ]
+        );
+
+        assert!(Template::parse("#ef git:HEAD:src/main.rs src/lib.rs").is_err());
+        assert!(Template::parse("#ef git:src/main.rs").is_err());
+        assert!(Template::parse("#ef rev=HEAD").is_err());
+    }
+
+    #[test]
+    fn test_parse_diff_directive() {
+        let template = Template::parse(
+            "#efdiff\n#efdiff main..HEAD -- src \"docs/a b.md\" context=1\n#efdiff staged=true",
+        )
+        .unwrap();
+        assert_eq!(
+            template.lines(),
+            &[
+                TemplateLine::Directive(Directive::Diff(DiffOptions::default())),
+                TemplateLine::Directive(Directive::Diff(DiffOptions {
+                    revisions: Some("main..HEAD".to_string()),
+                    pathspecs: vec!["src".to_string(), "docs/a b.md".to_string()],
+                    context: Some(1),
+                    staged: false,
+                })),
+                TemplateLine::Directive(Directive::Diff(DiffOptions {
+                    staged: true,
+                    ..DiffOptions::default()
+                })),
+            ]
+        );
+        assert!(Template::parse("#efdiff main HEAD").is_err());
+    }
+
+    #[test]
+    fn test_parse_changed_directive() {
+        let template = Template::parse("#efchanged\n#efchanged main untracked=true").unwrap();
+        assert_eq!(
+            template.lines(),
+            &[
+                TemplateLine::Directive(Directive::Changed {
+                    base: None,
+                    include_untracked: false,
+                }),
+                TemplateLine::Directive(Directive::Changed {
+                    base: Some("main".to_string()),
+                    include_untracked: true,
+                }),
+            ]
+        );
+        assert!(Template::parse("#efchanged main develop").is_err());
+    }
+
+    #[test]
+    fn test_variables() {
+        let content = "#efset module = parser\n#efset title = \"{{module}} module\"\n# {{ title }}\n#ef src/{{module}}/*.rs\n\\{{module}}";
+        let template = Template::parse(content).unwrap();
+        assert_eq!(
+            template.lines(),
+            &[
+                TemplateLine::Text("# parser module".to_string()),
+                TemplateLine::Directive(Directive::Glob("src/parser/*.rs".to_string())),
+                TemplateLine::Text("{{module}}".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_front_matter_variables() {
+        let content = "---\nvariables:\n  module: parser\n  title: review\n---\n#efset title = \"{{module}} module\"\n# {{title}}\n#ef src/{{module}}/*.rs";
+        let template = Template::parse(content).unwrap();
+        assert_eq!(
+            template.lines(),
+            &[
+                TemplateLine::Text("# parser module".to_string()),
+                TemplateLine::Directive(Directive::Glob("src/parser/*.rs".to_string())),
+            ]
+        );
+
+        let Err(Error::UndefinedVariables { variables }) =
+            Template::parse("---\nvariables:\n  module: parser\n---\n{{missing}}")
+        else {
+            panic!("expected undefined variables");
+        };
+        assert_eq!(variables[0].name, "missing");
+    }
+
+    #[test]
+    fn test_defines_override_variables() {
+        let dir = tempfile::tempdir().unwrap();
+        fs::write(
+            dir.path().join("main.txt"),
+            "#efset module = parser\n#ef src/{{module}}/*.rs",
+        )
+        .unwrap();
+
+        let defines = BTreeMap::from([("module".to_string(), "lexer".to_string())]);
+        let template = Template::from_file(dir.path().join("main.txt"), &defines).unwrap();
+        assert_eq!(
+            template.lines(),
+            &[TemplateLine::Directive(Directive::Glob(
+                "src/lexer/*.rs".to_string()
+            ))]
+        );
+
+        fs::write(
+            dir.path().join("front.txt"),
+            "---\nvariables:\n  module: parser\n---\n#ef src/{{module}}/*.rs",
+        )
+        .unwrap();
+        let template = Template::from_file(dir.path().join("front.txt"), &defines).unwrap();
+        assert_eq!(
+            template.lines(),
+            &[TemplateLine::Directive(Directive::Glob(
+                "src/lexer/*.rs".to_string()
+            ))]
+        );
+    }
+
+    #[test]
+    fn test_undefined_variables() {
+        let content = "---\nformat: xml\n---\n{{first}}\n#efset first = 1\n{{first}} {{second}}";
+        let Err(Error::UndefinedVariables { variables }) = Template::parse(content) else {
+            panic!("expected undefined variables");
+        };
+        let found = variables
+            .iter()
+            .map(|variable| (variable.name.as_str(), variable.location.line))
+            .collect::<Vec<_>>();
+        assert_eq!(found, vec![("first", 4), ("second", 6)]);
+    }
+
+    #[test]
+    fn test_references_without_variables_are_text() {
+        let content = "Render {{ title }} in the Vue component.";
+        let template = Template::parse(content).unwrap();
+        assert_eq!(template.lines(), &[TemplateLine::Text(content.to_string())]);
+
+        let dir = tempfile::tempdir().unwrap();
+        fs::write(dir.path().join("main.txt"), content).unwrap();
+        let defines = BTreeMap::from([("module".to_string(), "lexer".to_string())]);
+        assert!(matches!(
+            Template::from_file(dir.path().join("main.txt"), &defines),
+            Err(Error::UndefinedVariables { .. })
+        ));
+    }
+
+    #[test]
+    fn test_invalid_set_directive() {
+        assert!(Template::parse("#efset module").is_err());
+        assert!(Template::parse("#efset 1st = x").is_err());
+    }
+
+    #[test]
+    fn test_conditional_blocks() {
+        let dir = tempfile::tempdir().unwrap();
+        fs::create_dir_all(dir.path().join("prompts")).unwrap();
+        fs::write(dir.path().join("prompts/Cargo.toml"), "").unwrap();
+        fs::write(
+            dir.path().join("prompts/main.txt"),
+            "#efset mode = review\n#efif mode == review\nReview\n#efif exists(Cargo.toml)\n#ef src/*.rs\n#efendif\n#efif exists(package.json)\nPackage\n#efendif\n#efelse\n{{undefined}}\n#efendif\n#efif !env(EF_TEST_UNSET_VARIABLE)\nNo env\n#efendif",
+        )
+        .unwrap();
+
+        let template =
+            Template::from_file(dir.path().join("prompts/main.txt"), &BTreeMap::new()).unwrap();
+        assert_eq!(
+            template.lines(),
+            &[
+                TemplateLine::Text("Review".to_string()),
+                TemplateLine::Directive(Directive::Glob("src/*.rs".to_string())),
+                TemplateLine::Text("No env".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_conditional_blocks_skip_inactive_branches() {
+        let content = "#efset other = b\n#efif missing\n#efset mode = a\n#efif mode != a\nInner\n#efelse\nInner else\n#efendif\n#efelse\nOuter else {{mode}}\n#efendif";
+        let Err(Error::UndefinedVariables { variables }) = Template::parse(content) else {
+            panic!("expected the #efset in the skipped branch to be ignored");
+        };
+        assert_eq!(variables[0].location.line, 10);
+    }
+
+    #[test]
+    fn test_unbalanced_conditional_blocks() {
+        for (content, line) in [
+            ("Text\n#efif mode\nText", 2),
+            ("#efendif", 1),
+            ("Text\n#efelse", 2),
+            ("#efif mode\n#efelse\n#efelse\n#efendif", 3),
+            ("---\nformat: xml\n---\n#efif", 4),
+        ] {
+            let Err(Error::TemplateSyntax { location, .. }) = Template::parse(content) else {
+                panic!("expected a syntax error for {content:?}");
+            };
+            assert_eq!(location.line, line, "{content:?}");
+        }
+    }
+
+    #[test]
+    fn test_front_matter_is_stripped() {
+        let template = Template::parse("---\nformat: xml\n---\nText\n#ef src/*.rs").unwrap();
+        assert_eq!(template.front_matter().format.as_deref(), Some("xml"));
+        assert_eq!(
+            template.lines(),
+            &[
+                TemplateLine::Text("Text".to_string()),
+                TemplateLine::Directive(Directive::Glob("src/*.rs".to_string())),
+            ]
+        );
+    }
 }
@@ -0,0 +1,117 @@
+use std::path::{Path, PathBuf};
+
+/// ディレクトリツリーの要素
+#[derive(Debug, PartialEq)]
+pub struct TreeEntry {
+    pub path: PathBuf,
+    /// ディレクトリなら中身（深さの上限で展開しなかった場合は空）、ファイルなら`None`
+    pub children: Option<Vec<TreeEntry>>,
+}
+
+impl TreeEntry {
+    fn name(&self) -> String {
+        let name = self
+            .path
+            .file_name()
+            .map(|name| name.to_string_lossy().into_owned())
+            .unwrap_or_else(|| self.path.display().to_string());
+        match self.children {
+            Some(_) => format!("{}/", name),
+            None => name,
+        }
+    }
+}
+
+/// `tree`コマンド風のASCIIツリーを描画する
+///
+/// `annotate`がファイルに対して返した文字列は、名前の後ろに括弧書きで付ける。
+pub fn render(
+    root_label: &str,
+    entries: &[TreeEntry],
+    annotate: &dyn Fn(&Path) -> Option<String>,
+) -> String {
+    let mut lines = vec![root_label.to_string()];
+    render_entries(entries, "", annotate, &mut lines);
+    lines.join("\n")
+}
+
+fn render_entries(
+    entries: &[TreeEntry],
+    prefix: &str,
+    annotate: &dyn Fn(&Path) -> Option<String>,
+    lines: &mut Vec<String>,
+) {
+    for (index, entry) in entries.iter().enumerate() {
+        let is_last = index + 1 == entries.len();
+        let branch = if is_last { "└── " } else { "├── " };
+        let annotation = match entry.children {
+            Some(_) => None,
+            None => annotate(&entry.path),
+        };
+        lines.push(match annotation {
+            Some(annotation) => format!("{}{}{} ({})", prefix, branch, entry.name(), annotation),
+            None => format!("{}{}{}", prefix, branch, entry.name()),
+        });
+
+        if let Some(children) = &entry.children {
+            let prefix = format!("{}{}", prefix, if is_last { "    " } else { "│   " });
+            render_entries(children, &prefix, annotate, lines);
+        }
+    }
+}
+
+/// バイト数を`1.5 KB`のような読みやすい形にする
+pub fn format_size(bytes: u64) -> String {
+    const UNITS: [&str; 4] = ["KB", "MB", "GB", "TB"];
+    if bytes < 1024 {
+        return format!("{} B", bytes);
+    }
+
+    let mut size = bytes as f64 / 1024.0;
+    let mut unit = 0;
+    while size >= 1024.0 && unit + 1 < UNITS.len() {
+        size /= 1024.0;
+        unit += 1;
+    }
+    format!("{:.1} {}", size, UNITS[unit])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn file(path: &str) -> TreeEntry {
+        TreeEntry {
+            path: PathBuf::from(path),
+            children: None,
+        }
+    }
+
+    #[test]
+    fn test_render() {
+        let entries = vec![
+            TreeEntry {
+                path: PathBuf::from("src/parser"),
+                children: Some(vec![file("src/parser/lexer.rs"), file("src/parser/mod.rs")]),
+            },
+            TreeEntry {
+                path: PathBuf::from("src/empty"),
+                children: Some(Vec::new()),
+            },
+            file("src/main.rs"),
+        ];
+
+        let annotate = |path: &Path| (path.ends_with("main.rs")).then(|| "3 lines".to_string());
+        assert_eq!(
+            render("src/", &entries, &annotate),
+            "src/\n├── parser/\n│   ├── lexer.rs\n│   └── mod.rs\n├── empty/\n└── main.rs (3 lines)"
+        );
+    }
+
+    #[test]
+    fn test_format_size() {
+        assert_eq!(format_size(512), "512 B");
+        assert_eq!(format_size(1536), "1.5 KB");
+        assert_eq!(format_size(3 * 1024 * 1024), "3.0 MB");
+    }
+}
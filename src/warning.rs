@@ -1,3 +1,5 @@
+use crate::classify::FileClass;
+use std::collections::BTreeMap;
 use std::path::PathBuf;
 use thiserror::Error;
 
@@ -5,6 +7,89 @@ use thiserror::Error;
 pub enum Warning {
     #[error("File not found: {path}")]
     FileNotFound { path: PathBuf },
+
+    #[error("Line range {range} exceeds the length of {path} ({line_count} lines)")]
+    LineRangeOutOfBounds {
+        path: PathBuf,
+        range: String,
+        line_count: usize,
+    },
+
+    #[error("Region `{name}` not found in {path} (expected `ef:begin {name}` and `ef:end {name}` comments)")]
+    RegionNotFound { path: PathBuf, name: String },
+
+    #[error("Unbalanced region markers in {path}: {message}")]
+    UnbalancedRegion { path: PathBuf, message: String },
+
+    #[error("Symbol `{symbol}` not found in {path}")]
+    SymbolNotFound { path: PathBuf, symbol: String },
+
+    #[error("Cannot look up symbols in {path}: unsupported language `{language}` (supported: rust, typescript, javascript, python)")]
+    UnsupportedSymbolLanguage { path: PathBuf, language: String },
+
+    #[error("Cannot resolve Rust item {item}: {message}")]
+    RustItemNotFound { item: String, message: String },
+
+    #[error("Module declared in {declared_in} is missing: {message}")]
+    ModuleNotFound {
+        declared_in: PathBuf,
+        message: String,
+    },
+
+    #[error("Could not follow module declarations: {message}")]
+    InvalidRustSource { message: String },
+
+    #[error("Cannot resolve import \"{specifier}\" in {path}")]
+    DependencyNotFound { path: PathBuf, specifier: String },
+
+    #[error("Command `{command}` {status}")]
+    CommandFailed { command: String, status: String },
+
+    #[error("No changes found by git diff {revisions}")]
+    EmptyDiff { revisions: String },
+
+    #[error("Skipped deleted files: {}", format_paths(paths))]
+    DeletedFilesSkipped { paths: Vec<PathBuf> },
+
+    #[error(
+        "Skipped generated, vendored or documentation files (add include=<kinds> to the #ef or #efr directive to embed them):\n{}",
+        format_classified(skipped)
+    )]
+    ClassifiedFilesSkipped {
+        skipped: BTreeMap<FileClass, Vec<PathBuf>>,
+    },
+
+    #[error("Estimated token count ({estimated}) exceeds the token budget ({budget})")]
+    TokenBudgetExceeded { estimated: usize, budget: usize },
+}
+
+fn format_paths(paths: &[PathBuf]) -> String {
+    paths
+        .iter()
+        .map(|path| path.display().to_string())
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+/// 種類ごとに件数と先頭のいくつかのパスを並べる
+fn format_classified(skipped: &BTreeMap<FileClass, Vec<PathBuf>>) -> String {
+    const EXAMPLES: usize = 3;
+    skipped
+        .iter()
+        .map(|(class, paths)| {
+            let mut line = format!(
+                "  {} ({}): {}",
+                class,
+                paths.len(),
+                format_paths(&paths[..paths.len().min(EXAMPLES)])
+            );
+            if paths.len() > EXAMPLES {
+                line.push_str(&format!(" and {} more", paths.len() - EXAMPLES));
+            }
+            line
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
 }
 
 #[derive(Default)]
@@ -28,10 +113,10 @@ impl Warnings {
             return;
         }
 
-        eprintln!("{}", "\nWarnings:");
+        eprintln!("\nWarnings:");
 
         for warning in &self.0 {
-            eprintln!("{} {}", "⚠", "─".repeat(50));
+            eprintln!("⚠ {}", "─".repeat(50));
             let message = format!("{}", warning);
 
             for line in message.lines() {
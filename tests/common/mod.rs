@@ -1,6 +1,7 @@
 use std::env;
 use std::fs;
 use std::path::{Path, PathBuf};
+use std::process::Command;
 use tempfile::{tempdir, TempDir};
 
 pub struct TestEnv {
@@ -53,6 +54,29 @@ impl TestEnv {
         self.create_file(".eftemplate", content)
     }
 
+    /// テスト用のディレクトリでgitコマンドを実行する
+    pub fn git(&self, args: &[&str]) -> String {
+        let output = Command::new("git")
+            .current_dir(&self.base_path)
+            .args(["-c", "user.name=ef", "-c", "user.email=ef@example.com"])
+            .args([
+                "-c",
+                "commit.gpgsign=false",
+                "-c",
+                "init.defaultBranch=main",
+            ])
+            .args(args)
+            .output()
+            .expect("Failed to run git");
+        assert!(
+            output.status.success(),
+            "git {:?} failed: {}",
+            args,
+            String::from_utf8_lossy(&output.stderr)
+        );
+        String::from_utf8_lossy(&output.stdout).into_owned()
+    }
+
     pub fn run_test_in_scope<F, R>(&self, test_fn: F) -> R
     where
         F: FnOnce() -> R,
@@ -1,4 +1,6 @@
+use ef::path_resolver::PathResolver;
 use ef::run_with_args;
+use ef::warning::Warning;
 mod common;
 use common::TestEnv;
 
@@ -107,6 +109,7 @@ fn test_no_eftemplate_uses_default() {
 
         assert!(output.contains("Main source:"));
         assert!(output.contains("src/main.rs"));
+        assert!(output.contains("```rust\nfn main() {"));
     })
 }
 
@@ -204,3 +207,633 @@ fn test_template_in_different_directory() {
         ));
     })
 }
+
+#[test]
+fn test_front_matter_settings() {
+    let env = TestEnv::new();
+    env.run_test_in_scope(|| {
+        let template = env.create_template(
+            r#"---
+format: xml
+ignore:
+  - lib.rs
+variables:
+  unused: value
+---
+Sources:
+#ef src/*.rs
+"#,
+        );
+
+        common::setup_sample_files(&env);
+        env.create_eftemplate("IGNORED: {filePath}\n{content}");
+
+        let args = vec!["ef".to_string(), template.to_str().unwrap().to_string()];
+        let output = run_with_args(args).unwrap();
+
+        assert!(output.starts_with("Sources:\n"));
+        assert!(!output.contains("format: xml"));
+        assert!(!output.contains("IGNORED: "));
+        assert!(output.contains(r#"<file path="src/main.rs" language="rust">"#));
+        assert!(!output.contains("src/lib.rs"));
+    })
+}
+
+#[test]
+fn test_front_matter_eftemplate_and_path_base() {
+    let env = TestEnv::new();
+    env.run_test_in_scope(|| {
+        let template = env.create_file(
+            "prompts/template.txt",
+            r#"+++
+eftemplate = "review.eftemplate"
+path_base = "../src"
+variables = { reviewer = "moai" }
++++
+#ef src/main.rs
+"#,
+        );
+        env.create_file(
+            "prompts/review.eftemplate",
+            "{reviewer}: {filePath}\n{content}",
+        );
+
+        common::setup_sample_files(&env);
+
+        let args = vec!["ef".to_string(), template.to_str().unwrap().to_string()];
+        let output = run_with_args(args).unwrap();
+
+        assert!(output.contains("moai: main.rs\n"));
+    })
+}
+
+#[test]
+fn test_exclude_directive() {
+    let env = TestEnv::new();
+    env.run_test_in_scope(|| {
+        let template = env.create_template(
+            r#"Before exclusion:
+#ef src/*.rs
+#efx lib.rs
+After exclusion:
+#ef src/*.rs
+Inline exclusion:
+#ef src/*.rs !main.rs
+"#,
+        );
+
+        common::setup_sample_files(&env);
+        env.create_eftemplate("FILE: {filePath}");
+
+        let args = vec!["ef".to_string(), template.to_str().unwrap().to_string()];
+        let output = run_with_args(args).unwrap();
+
+        let sections = output.split("After exclusion:").collect::<Vec<_>>();
+        assert!(sections[0].contains("FILE: src/lib.rs"));
+        assert!(sections[0].contains("FILE: src/main.rs"));
+
+        let sections = sections[1].split("Inline exclusion:").collect::<Vec<_>>();
+        assert!(!sections[0].contains("FILE: src/lib.rs"));
+        assert!(sections[0].contains("FILE: src/main.rs"));
+        assert!(!sections[1].contains("FILE: "));
+    })
+}
+
+#[test]
+fn test_line_range() {
+    let env = TestEnv::new();
+    env.run_test_in_scope(|| {
+        let template = env.create_template("#ef src/lines.rs:2-3\n#ef src/lines.rs:4-\n");
+        env.create_file("src/lines.rs", "one\ntwo\nthree\nfour\n");
+        env.create_eftemplate("{filePath}:{startLine}-{endLine} [{language}]\n{content}");
+
+        let args = vec!["ef".to_string(), template.to_str().unwrap().to_string()];
+        let output = run_with_args(args).unwrap();
+
+        assert!(output.contains("src/lines.rs:2-3 [rust]\ntwo\nthree\n"));
+        assert!(output.contains("src/lines.rs:4-4 [rust]\nfour\n"));
+        assert!(!output.contains("one"));
+    })
+}
+
+#[test]
+fn test_region_directive() {
+    let env = TestEnv::new();
+    env.run_test_in_scope(|| {
+        let template =
+            env.create_template("#efregion src/region.py#setup\n#efregion src/region.py#missing\n");
+        env.create_file(
+            "src/region.py",
+            "import os\n# ef:begin setup\nconfig = load()\n# ef:end setup\nrun(config)\n",
+        );
+        env.create_eftemplate("{filePath}#{region}:{startLine}-{endLine}\n{content}");
+
+        let args = vec!["ef".to_string(), template.to_str().unwrap().to_string()];
+        let output = run_with_args(args).unwrap();
+
+        assert_eq!(output, "src/region.py#setup:3-3\nconfig = load()\n");
+    })
+}
+
+#[test]
+fn test_symbol_directive() {
+    let env = TestEnv::new();
+    env.run_test_in_scope(|| {
+        let template = env.create_template("#efsym src/config.rs::Config::new\n");
+        env.create_file(
+            "src/config.rs",
+            "pub struct Config;\n\nimpl Config {\n    /// Creates a config\n    pub fn new() -> Self {\n        Config\n    }\n}\n",
+        );
+        env.create_eftemplate("{symbol} ({language}) {startLine}-{endLine}\n{content}");
+
+        let args = vec!["ef".to_string(), template.to_str().unwrap().to_string()];
+        let output = run_with_args(args).unwrap();
+
+        assert_eq!(
+            output,
+            "Config::new (rust) 4-7\n    /// Creates a config\n    pub fn new() -> Self {\n        Config\n    }\n"
+        );
+    })
+}
+
+#[test]
+fn test_rust_item_directive() {
+    let env = TestEnv::new();
+    env.run_test_in_scope(|| {
+        let template = env.create_template(
+            "#efrs crate::config::Config\n#efrs crate::config tests=false\n#efrs crate::missing::Item\n",
+        );
+        env.create_file("Cargo.toml", "[package]\nname = \"sample\"\n");
+        env.create_file("src/lib.rs", "pub mod config;\n");
+        env.create_file(
+            "src/config.rs",
+            "pub struct Config;\n\nfn other() {}\n\nimpl Config {\n    pub fn new() -> Self {\n        Config\n    }\n}\n\n#[cfg(test)]\nmod tests {}\n",
+        );
+        env.create_eftemplate("[{symbol}] {filePath}:{startLine}-{endLine}\n{content}");
+
+        let args = vec!["ef".to_string(), template.to_str().unwrap().to_string()];
+        let output = run_with_args(args).unwrap();
+
+        let expected_item = "[crate::config::Config] src/config.rs:1-9\npub struct Config;\n\nimpl Config {\n    pub fn new() -> Self {\n        Config\n    }\n}\n";
+        assert!(output.starts_with(expected_item));
+        assert!(output.contains("[crate::config] src/config.rs:1-12\npub struct Config;\n\nfn other() {}"));
+        assert!(!output.contains("mod tests"));
+        assert!(!output.contains("crate::missing"));
+    })
+}
+
+#[test]
+fn test_module_tree_directive() {
+    let env = TestEnv::new();
+    env.run_test_in_scope(|| {
+        let template = env.create_template("#efmod src/lib.rs\n");
+        env.create_file("src/lib.rs", "mod b;\nmod a;\n");
+        env.create_file("src/a.rs", "");
+        env.create_file("src/b.rs", "");
+        env.create_file("src/bin/tool.rs", "");
+        env.create_eftemplate("FILE: {filePath}");
+
+        let args = vec!["ef".to_string(), template.to_str().unwrap().to_string()];
+        let output = run_with_args(args).unwrap();
+
+        assert_eq!(output, "FILE: src/lib.rs\nFILE: src/b.rs\nFILE: src/a.rs\n");
+    })
+}
+
+#[test]
+fn test_dependencies_directive() {
+    let env = TestEnv::new();
+    env.run_test_in_scope(|| {
+        let template = env.create_template("#efdeps src/components/TaskList.tsx depth=2\n");
+        env.create_file(
+            "tsconfig.json",
+            r#"{ "compilerOptions": { "paths": { "@/*": ["src/*"] } } }"#,
+        );
+        env.create_file(
+            "src/components/TaskList.tsx",
+            "import React from 'react';\nimport { TaskItem } from './TaskItem';\nimport { useTasks } from '@/hooks/useTasks';\n",
+        );
+        env.create_file("src/components/TaskItem.tsx", "import { api } from '../api';\n");
+        env.create_file("src/hooks/useTasks.ts", "");
+        env.create_file("src/api/index.ts", "import { deep } from './deep';\n");
+        env.create_file("src/api/deep.ts", "");
+        env.create_file("node_modules/react/index.js", "");
+        env.create_eftemplate("FILE: {filePath}");
+
+        let args = vec!["ef".to_string(), template.to_str().unwrap().to_string()];
+        let output = run_with_args(args).unwrap();
+
+        assert_eq!(
+            output,
+            "FILE: src/components/TaskList.tsx\nFILE: src/components/TaskItem.tsx\nFILE: src/hooks/useTasks.ts\nFILE: src/api/index.ts\n"
+        );
+    })
+}
+
+#[test]
+fn test_python_dependencies_directive() {
+    let env = TestEnv::new();
+    env.run_test_in_scope(|| {
+        let template = env.create_template("#efdeps app/main.py\n");
+        env.create_file("pyproject.toml", "");
+        env.create_file(
+            "app/main.py",
+            "import os\nfrom app.services import user_service\nfrom .models import User\n",
+        );
+        env.create_file("app/__init__.py", "");
+        env.create_file("app/models.py", "");
+        env.create_file("app/services/__init__.py", "");
+        env.create_file(
+            "app/services/user_service.py",
+            "from ..models import User\n",
+        );
+        env.create_eftemplate("FILE: {filePath}");
+
+        let args = vec!["ef".to_string(), template.to_str().unwrap().to_string()];
+        let output = run_with_args(args).unwrap();
+
+        assert_eq!(
+            output,
+            "FILE: app/main.py\nFILE: app/services/user_service.py\nFILE: app/models.py\n"
+        );
+    })
+}
+
+#[test]
+fn test_include_directive() {
+    let env = TestEnv::new();
+    env.run_test_in_scope(|| {
+        let template = env.create_template("#efinclude prompts/preamble.txt\nQuestion\n");
+        env.create_file("prompts/preamble.txt", "Conventions\n#ef src/main.rs\n");
+        common::setup_sample_files(&env);
+        env.create_eftemplate("FILE: {filePath}");
+
+        let args = vec!["ef".to_string(), template.to_str().unwrap().to_string()];
+        let output = run_with_args(args).unwrap();
+        assert_eq!(output, "Conventions\nFILE: src/main.rs\nQuestion\n");
+
+        env.create_file("prompts/preamble.txt", "#efinclude ../template.txt\n");
+        let args = vec!["ef".to_string(), template.to_str().unwrap().to_string()];
+        let error = run_with_args(args).unwrap_err();
+        assert!(error.to_string().starts_with("Include cycle detected: "));
+        assert!(error.to_string().contains("preamble.txt -> "));
+    })
+}
+
+#[test]
+fn test_template_variables() {
+    let env = TestEnv::new();
+    env.run_test_in_scope(|| {
+        let template =
+            env.create_template("#efset dir = src\nFiles in {{dir}}:\n#ef {{dir}}/{{name}}.rs\n");
+        common::setup_sample_files(&env);
+        env.create_eftemplate("FILE: {filePath}");
+
+        let args = vec![
+            "ef".to_string(),
+            template.to_str().unwrap().to_string(),
+            "-D".to_string(),
+            "name=main".to_string(),
+        ];
+        let output = run_with_args(args).unwrap();
+        assert_eq!(output, "Files in src:\nFILE: src/main.rs\n");
+
+        let args = vec!["ef".to_string(), template.to_str().unwrap().to_string()];
+        let error = run_with_args(args).unwrap_err();
+        assert!(error
+            .to_string()
+            .starts_with("Undefined template variables: name ("));
+        assert!(error.to_string().ends_with("template.txt:3)"));
+    })
+}
+
+#[test]
+fn test_conditional_blocks() {
+    let env = TestEnv::new();
+    env.run_test_in_scope(|| {
+        let template = env.create_template(
+            "#efif mode == full\n#ef src/*.rs\n#efelse\n#ef src/main.rs\n#efendif\n#efif exists(README.md)\nREADME\n#efendif\n",
+        );
+        common::setup_sample_files(&env);
+        env.create_eftemplate("FILE: {filePath}");
+
+        let args = vec!["ef".to_string(), template.to_str().unwrap().to_string()];
+        let output = run_with_args(args).unwrap();
+        assert_eq!(output, "FILE: src/main.rs\n");
+
+        env.create_file("README.md", "");
+        let args = vec![
+            "ef".to_string(),
+            template.to_str().unwrap().to_string(),
+            "-D".to_string(),
+            "mode=full".to_string(),
+        ];
+        let output = run_with_args(args).unwrap();
+        assert!(output.contains("FILE: src/lib.rs\n"));
+        assert!(output.ends_with("README\n"));
+
+        let template = env.create_template("#efif mode\nText\n");
+        let args = vec!["ef".to_string(), template.to_str().unwrap().to_string()];
+        let error = run_with_args(args).unwrap_err();
+        assert!(error.to_string().starts_with("Template syntax error at "));
+        assert!(error
+            .to_string()
+            .ends_with("template.txt:1: #efif is not closed by #efendif"));
+    })
+}
+
+#[cfg(unix)]
+#[test]
+fn test_command_directive() {
+    let env = TestEnv::new();
+    env.run_test_in_scope(|| {
+        let template = env.create_template("#efcmd ls src\n#efcmd echo broken >&2; exit 2\n");
+        common::setup_sample_files(&env);
+        env.create_eftemplate(
+            "FILE: {filePath}\n#efsection command\n$ {command} [{exitCode}]\n{output}",
+        );
+
+        let args = vec!["ef".to_string(), template.to_str().unwrap().to_string()];
+        let error = run_with_args(args).unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "#efcmd is disabled: pass --allow-commands to run `ls src`"
+        );
+
+        let args = vec![
+            "ef".to_string(),
+            template.to_str().unwrap().to_string(),
+            "--allow-commands".to_string(),
+        ];
+        let output = run_with_args(args).unwrap();
+        assert_eq!(
+            output,
+            "$ ls src [0]\nlib.rs\nmain.rs\n$ echo broken >&2; exit 2 [2]\nbroken\n"
+        );
+    })
+}
+
+#[test]
+fn test_tree_directive() {
+    let env = TestEnv::new();
+    env.run_test_in_scope(|| {
+        let template = env.create_template("#eftree src lines=true\n#eftree . depth=1\n");
+        common::setup_sample_files(&env);
+        env.create_file("src/nested/mod.rs", "");
+        env.create_file("src/.hidden", "");
+        env.create_eftemplate("FILE: {filePath}\n#efsection tree\nTREE {root}\n{tree}");
+
+        let args = vec!["ef".to_string(), template.to_str().unwrap().to_string()];
+        let output = run_with_args(args).unwrap();
+        assert_eq!(
+            output,
+            "TREE src/\nsrc/\n├── nested/\n│   └── mod.rs (0 lines)\n├── lib.rs (3 lines)\n└── main.rs (3 lines)\n\
+TREE ./\n./\n├── src/\n└── template.txt\n"
+        );
+    })
+}
+
+#[test]
+fn test_diff_directive() {
+    let env = TestEnv::new();
+    env.run_test_in_scope(|| {
+        common::setup_sample_files(&env);
+        env.create_file("docs/old.md", "old\n");
+        env.create_file("a b.txt", "before\n");
+        env.git(&["init", "--quiet"]);
+        env.git(&["add", "."]);
+        env.git(&["commit", "--quiet", "-m", "initial"]);
+
+        env.create_file(
+            "src/main.rs",
+            "fn main() {\n    println!(\"Hello, ef!\");\n}",
+        );
+        env.create_file("a b.txt", "after\n");
+        env.git(&["rm", "--quiet", "docs/old.md"]);
+        env.create_eftemplate("FILE: {filePath} ({language})\n{content}");
+
+        let template = env.create_template("#efdiff context=0 -- src\n");
+        let args = vec!["ef".to_string(), template.to_str().unwrap().to_string()];
+        let output = run_with_args(args).unwrap();
+        assert!(output
+            .starts_with("FILE: src/main.rs (diff)\ndiff --git a/src/main.rs b/src/main.rs\n"));
+        assert!(output.contains(
+            "@@ -2 +2 @@ fn main() {\n-    println!(\"Hello, world!\");\n+    println!(\"Hello, ef!\");\n"
+        ));
+        assert!(!output.contains("old.md"));
+
+        // 空白を含むパスもそのまま表示する
+        let template = env.create_template("#efdiff -- \"a b.txt\"\n");
+        let args = vec!["ef".to_string(), template.to_str().unwrap().to_string()];
+        let output = run_with_args(args).unwrap();
+        assert!(output.starts_with("FILE: a b.txt (diff)\n"));
+        assert!(output.contains("+after\n"));
+
+        let template = env.create_template("#efdiff staged=true\n");
+        let args = vec!["ef".to_string(), template.to_str().unwrap().to_string()];
+        let output = run_with_args(args).unwrap();
+        assert!(output.starts_with("FILE: docs/old.md (diff)\n"));
+        assert!(!output.contains("src/main.rs"));
+
+        let template = env.create_template("#efdiff no-such-ref\n");
+        let args = vec!["ef".to_string(), template.to_str().unwrap().to_string()];
+        let error = run_with_args(args).unwrap_err();
+        assert!(error.to_string().starts_with("git diff "));
+    })
+}
+
+#[test]
+fn test_changed_directive() {
+    let env = TestEnv::new();
+    env.run_test_in_scope(|| {
+        common::setup_sample_files(&env);
+        env.create_file("docs/old.md", "old\n");
+        env.create_file(".gitignore", "*.log\n");
+        env.git(&["init", "--quiet"]);
+        env.git(&["add", "."]);
+        env.git(&["commit", "--quiet", "-m", "initial"]);
+
+        env.create_file("src/main.rs", "fn main() {}\n");
+        env.create_file("src/new.rs", "pub fn new() {}\n");
+        env.create_file("debug.log", "ignored\n");
+        env.git(&["rm", "--quiet", "docs/old.md"]);
+        env.create_eftemplate("FILE: {filePath}\n{content}");
+
+        let template = env.create_template("#efchanged\n");
+        let args = vec!["ef".to_string(), template.to_str().unwrap().to_string()];
+        let output = run_with_args(args).unwrap();
+        assert_eq!(output, "FILE: src/main.rs\nfn main() {}\n\n");
+
+        let template = env.create_template("#efchanged HEAD untracked=true\n");
+        let args = vec!["ef".to_string(), template.to_str().unwrap().to_string()];
+        let output = run_with_args(args).unwrap();
+        assert!(output.contains("FILE: src/main.rs\n"));
+        assert!(output.contains("FILE: src/new.rs\n"));
+        assert!(output.contains("FILE: template.txt\n"));
+        assert!(!output.contains("debug.log"));
+        assert!(!output.contains("old.md"));
+
+        // 削除されたファイルは警告に名前が表示される
+        let mut resolver = PathResolver::new().unwrap();
+        resolver.resolve_changed(None, false).unwrap();
+        let deleted = resolver
+            .take_warnings()
+            .into_iter()
+            .find_map(|warning| match warning {
+                Warning::DeletedFilesSkipped { paths } => Some(paths),
+                _ => None,
+            })
+            .expect("expected a warning about the deleted file");
+        assert_eq!(deleted, vec![std::path::PathBuf::from("docs/old.md")]);
+    })
+}
+
+#[test]
+fn test_revision_paths() {
+    let env = TestEnv::new();
+    env.run_test_in_scope(|| {
+        common::setup_sample_files(&env);
+        env.git(&["init", "--quiet"]);
+        env.git(&["add", "."]);
+        env.git(&["commit", "--quiet", "-m", "initial"]);
+        env.create_file("src/main.rs", "fn main() {}\n");
+        env.git(&["commit", "--quiet", "-am", "simplify"]);
+        env.create_eftemplate("FILE: {filePath} ({language})\n{content}");
+
+        let template =
+            env.create_template("#ef git:HEAD~1:src/main.rs\n#ef src/main.rs rev=HEAD\n");
+        let args = vec!["ef".to_string(), template.to_str().unwrap().to_string()];
+        let output = run_with_args(args).unwrap();
+        assert_eq!(
+            output,
+            "FILE: src/main.rs@HEAD~1 (rust)\nfn main() {\n    println!(\"Hello, world!\");\n}\n\
+FILE: src/main.rs@HEAD (rust)\nfn main() {}\n\n"
+        );
+
+        let template = env.create_template("#ef git:HEAD~1:src/*.rs\n");
+        let args = vec!["ef".to_string(), template.to_str().unwrap().to_string()];
+        let output = run_with_args(args).unwrap();
+        assert!(output.starts_with("FILE: src/lib.rs@HEAD~1 (rust)\n"));
+        assert!(output.contains("FILE: src/main.rs@HEAD~1 (rust)\n"));
+    })
+}
+
+#[test]
+fn test_git_selection_modes() {
+    let env = TestEnv::new();
+    env.run_test_in_scope(|| {
+        common::setup_sample_files(&env);
+        env.create_file(".gitignore", "target/\n");
+        env.git(&["init", "--quiet"]);
+        env.git(&["add", "."]);
+        env.git(&["commit", "--quiet", "-m", "initial"]);
+
+        env.create_file("target/debug/build.rs", "// generated\n");
+        env.create_file("src/untracked.rs", "// new\n");
+        env.create_file("src/lib.rs", "// staged\n");
+        env.git(&["add", "src/lib.rs"]);
+        env.create_file("src/main.rs", "// modified\n");
+        env.create_eftemplate("FILE: {filePath}");
+
+        let run = |template: &str, extra: &[&str]| {
+            let template = env.create_template(template);
+            let mut args = vec!["ef".to_string(), template.to_str().unwrap().to_string()];
+            args.extend(extra.iter().map(|arg| arg.to_string()));
+            run_with_args(args).unwrap()
+        };
+
+        assert_eq!(
+            run("#efr \\.rs$ select=tracked-only\n", &[]),
+            "FILE: src/lib.rs\nFILE: src/main.rs\n"
+        );
+        assert_eq!(
+            run("#ef **/*.rs select=include-untracked\n", &[]),
+            "FILE: src/lib.rs\nFILE: src/main.rs\nFILE: src/untracked.rs\n"
+        );
+        assert_eq!(
+            run("#ef src/*.rs\n", &["--select", "staged"]),
+            "FILE: src/lib.rs\n"
+        );
+        assert_eq!(
+            run("#ef src/*.rs\n", &["--select", "modified"]),
+            "FILE: src/lib.rs\nFILE: src/main.rs\n"
+        );
+        // `..`を含むパターンでも絞り込める
+        let output = run("#ef src/../src/*.rs select=tracked-only\n", &[]);
+        assert_eq!(output.matches("FILE: ").count(), 2);
+        assert!(output.contains("lib.rs\n"));
+        assert!(output.contains("main.rs\n"));
+        assert!(!output.contains("untracked.rs"));
+        // 指示子の指定がCLIより優先される
+        assert_eq!(
+            run("#ef src/*.rs select=all\n", &["--select", "staged"]),
+            "FILE: src/lib.rs\nFILE: src/main.rs\nFILE: src/untracked.rs\n"
+        );
+
+        let template = env.create_template("#ef src/*.rs select=everything\n");
+        let args = vec!["ef".to_string(), template.to_str().unwrap().to_string()];
+        assert!(run_with_args(args).is_err());
+    })
+}
+
+#[test]
+fn test_ignore_files() {
+    let env = TestEnv::new();
+    env.run_test_in_scope(|| {
+        common::setup_sample_files(&env);
+        env.git(&["init", "--quiet"]);
+        env.create_file(".gitignore", "target/\n");
+        env.create_file("src/.gitignore", "generated.rs\n");
+        env.create_file(".efignore", "src/lib.rs\n");
+        env.create_file("target/debug/build.rs", "// build\n");
+        env.create_file("src/generated.rs", "// generated\n");
+        env.create_eftemplate("FILE: {filePath}");
+
+        let template = env.create_template("#efr \\.rs$\n");
+        let args = vec!["ef".to_string(), template.to_str().unwrap().to_string()];
+        let output = run_with_args(args).unwrap();
+        assert_eq!(output, "FILE: src/main.rs\n");
+
+        let args = vec![
+            "ef".to_string(),
+            template.to_str().unwrap().to_string(),
+            "--no-ignore".to_string(),
+        ];
+        let output = run_with_args(args).unwrap();
+        assert!(output.contains("FILE: target/debug/build.rs\n"));
+        assert!(output.contains("FILE: src/generated.rs\n"));
+        assert!(output.contains("FILE: src/lib.rs\n"));
+    })
+}
+
+#[test]
+fn test_classified_files() {
+    let env = TestEnv::new();
+    env.run_test_in_scope(|| {
+        common::setup_sample_files(&env);
+        env.git(&["init", "--quiet"]);
+        env.create_file(".gitattributes", "src/schema/** linguist-generated\n");
+        env.create_file("src/schema/tables.rs", "pub struct Table;\n");
+        env.create_file("src/bindings.rs", "// @generated by bindgen\n");
+        env.create_file("third_party/zlib/src/zlib.rs", "");
+        env.create_file(
+            "Cargo.lock",
+            "# This file is automatically @generated by Cargo.\n",
+        );
+        env.create_eftemplate("FILE: {filePath}");
+
+        let template = env.create_template("#ef **/*.rs\n#efr \\.lock$\n");
+        let args = vec!["ef".to_string(), template.to_str().unwrap().to_string()];
+        let output = run_with_args(args).unwrap();
+        assert_eq!(output, "FILE: src/lib.rs\nFILE: src/main.rs\n");
+
+        let template =
+            env.create_template("#ef **/*.rs include=generated\n#efr \\.lock$ include=all\n");
+        let args = vec!["ef".to_string(), template.to_str().unwrap().to_string()];
+        let output = run_with_args(args).unwrap();
+        assert!(output.contains("FILE: src/schema/tables.rs\n"));
+        assert!(output.contains("FILE: src/bindings.rs\n"));
+        assert!(!output.contains("zlib.rs"));
+        assert!(output.contains("FILE: Cargo.lock\n"));
+    })
+}